@@ -1,12 +1,16 @@
 //! Deezer platform implementation
 //! Useful link https://developers.deezer.com/api
 
-use reqwest::Client;
 use std::{future::Future, pin::Pin};
 
 use super::types::ApiResponse;
 use crate::{
-    custom_env, errors::MusicExporterError, utils::input_env, Music, MusicExporter, Platform,
+    custom_env,
+    errors::MusicExporterError,
+    http::HttpClient,
+    pagination::{Cursor, Page, Paginator},
+    utils::prompt_env,
+    Music, MusicExporter, Platform, PlatformType,
 };
 
 /// Deezer platform implementation
@@ -17,16 +21,16 @@ pub struct DeezerPlatform {
 
     /// Deezer user id
     user_id: String,
+
+    /// HTTP client
+    http: HttpClient,
 }
 
 impl DeezerPlatform {
     /// Get playlist items
     /// # Errors
     /// Error if the response is not a valid json
-    async fn get_playlist_items(
-        &self,
-        offset: Option<u64>,
-    ) -> Result<(Vec<Music>, Option<u64>), MusicExporterError> {
+    async fn get_playlist_items(&self, offset: Option<u64>) -> Result<Page, MusicExporterError> {
         let url = url::Url::parse_with_params(
             &format!("https://api.deezer.com/user/{}/tracks", self.user_id),
             &[
@@ -34,14 +38,16 @@ impl DeezerPlatform {
                 ("limit", 50.to_string()), // 50 is the maximum
             ],
         )?;
-        let resp = Client::new()
+        let request = self
+            .http
             .get(url)
             .header("cookie", &self.cookie)
-            .header("Accept", "application/json")
-            .send()
-            .await?;
-        let text_resp = resp.text().await?;
-        let json_response = serde_json::from_str::<ApiResponse>(&text_resp)?;
+            .header("Accept", "application/json");
+        let json_response = self
+            .http
+            .fetch_json::<ApiResponse>(PlatformType::Deezer, request)
+            .await
+            .map_err(|e| MusicExporterError::new_with_source("Failed to get the user tracks", e))?;
         let items = json_response
             .data
             .iter()
@@ -52,6 +58,7 @@ impl DeezerPlatform {
                 url: Some(item.link.clone()),
                 date: None,
                 album: Some(item.album.title.clone()),
+                ..Default::default()
             })
             .collect();
         let next_offset = match json_response.next {
@@ -68,43 +75,48 @@ impl DeezerPlatform {
             None => None,
         };
         log::info!("Next offset: {:?}", next_offset);
-        Ok((items, next_offset))
+        Ok(Page {
+            items,
+            next: next_offset.map(Cursor::Offset),
+            total: Some(json_response.total),
+        })
     }
 }
 
 impl Platform for DeezerPlatform {
     fn try_new(
-        _music_exp: &MusicExporter,
+        music_exp: &MusicExporter,
     ) -> Pin<Box<dyn Future<Output = Result<Self, MusicExporterError>> + Send>> {
-        Box::pin(async {
-            let cookie = input_env(
+        let http = music_exp.http_client();
+        Box::pin(async move {
+            let http = http?;
+            let cookie = prompt_env(
                 "Please enter your deezer cookie",
                 custom_env!("DEEZER_COOKIE"),
-            )?;
-            let user_id = input_env(
+            )
+            .await?;
+            let user_id = prompt_env(
                 "Please enter your deezer user id",
                 custom_env!("DEEZER_USER_ID"),
-            )?;
+            )
+            .await?;
 
-            Ok(Self { cookie, user_id })
+            Ok(Self {
+                cookie,
+                user_id,
+                http,
+            })
         })
     }
 
     fn get_list<'a>(
         &'a self,
+        paginator: &'a Paginator,
     ) -> Pin<Box<dyn Future<Output = Result<Vec<Music>, MusicExporterError>> + Send + 'a>> {
         Box::pin(async {
-            let mut items = Vec::new();
-            let mut page_next = None;
-            loop {
-                let (new_items, next_page) = self.get_playlist_items(page_next).await?;
-                items.extend(new_items);
-                page_next = next_page;
-                if page_next.is_none() {
-                    break;
-                }
-            }
-            Ok(items)
+            paginator
+                .collect(|cursor| self.get_playlist_items(cursor.and_then(|c| c.offset())))
+                .await
         })
     }
 }
@@ -20,6 +20,7 @@ pub struct Album {
     // pub cover_xl: String,
 }
 
+/// Track item
 #[derive(Debug, serde::Deserialize)]
 pub struct TrackItem {
     // pub id: i64,
@@ -40,11 +41,15 @@ pub struct TrackItem {
     pub artist: Artist,
 }
 
+/// API response of the user tracks
 #[derive(Debug, serde::Deserialize)]
 pub struct ApiResponse {
     /// Next page URL
     pub next: Option<String>,
-    // pub total: u64,
+
+    /// Total number of tracks
+    pub total: u64,
+
     /// Track items
     pub data: Vec<TrackItem>,
 }
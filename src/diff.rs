@@ -0,0 +1,231 @@
+//! Differences between two lists of musics
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+use crate::Music;
+
+/// Output format of a diff
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[non_exhaustive]
+pub enum DiffFormat {
+    /// Plain text, one music per line
+    #[default]
+    Text,
+
+    /// JSON object
+    Json,
+
+    /// Markdown sections, for changelogs
+    Markdown,
+}
+
+/// Music found in both lists with different metadata
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MusicChange {
+    /// Music in the old list
+    pub before: Music,
+
+    /// Music in the new list
+    pub after: Music,
+
+    /// Names of the fields that changed
+    pub fields: Vec<&'static str>,
+}
+
+/// Differences between two lists of musics
+///
+/// Musics are matched like [`Music::eq`], on their normalized title and author
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct MusicDiff {
+    /// Musics only in the new list
+    pub added: Vec<Music>,
+
+    /// Musics only in the old list
+    pub removed: Vec<Music>,
+
+    /// Musics in both lists with different metadata
+    pub changed: Vec<MusicChange>,
+}
+
+impl MusicDiff {
+    /// Compare two lists of musics
+    pub fn new(old: &[Music], new: &[Music]) -> Self {
+        let mut old_by_key = HashMap::new();
+        for music in old {
+            old_by_key.entry(music.key()).or_insert(music);
+        }
+        let new_keys = new.iter().map(Music::key).collect::<HashSet<_>>();
+        let changed = unique_in_order(new, |music| old_by_key.contains_key(&music.key()))
+            .into_iter()
+            .filter_map(|after| {
+                let before = old_by_key[&after.key()].clone();
+                let fields = changed_fields(&before, &after);
+                (!fields.is_empty()).then_some(MusicChange {
+                    before,
+                    after,
+                    fields,
+                })
+            })
+            .collect();
+        Self {
+            added: unique_in_order(new, |music| !old_by_key.contains_key(&music.key())),
+            removed: unique_in_order(old, |music| !new_keys.contains(&music.key())),
+            changed,
+        }
+    }
+
+    /// Whether the lists contain the same musics
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Render the diff in the given format
+    /// # Errors
+    /// Error if the diff cannot be serialized to JSON
+    pub fn render(&self, format: DiffFormat) -> Result<String, serde_json::Error> {
+        match format {
+            DiffFormat::Text => Ok(self.to_text()),
+            DiffFormat::Json => serde_json::to_string_pretty(self).map(|json| json + "\n"),
+            DiffFormat::Markdown => Ok(self.to_markdown()),
+        }
+    }
+
+    /// Plain text diff
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+        for music in &self.added {
+            let _ = writeln!(out, "+ {} by {}", music.title, music.author);
+        }
+        for music in &self.removed {
+            let _ = writeln!(out, "- {} by {}", music.title, music.author);
+        }
+        for change in &self.changed {
+            let _ = writeln!(
+                out,
+                "~ {} by {} ({})",
+                change.after.title,
+                change.after.author,
+                change.fields.join(", ")
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{} added, {} removed, {} changed",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        );
+        out
+    }
+
+    /// Markdown diff
+    fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let sections = [("Added", &self.added), ("Removed", &self.removed)];
+        for (name, musics) in sections {
+            if musics.is_empty() {
+                continue;
+            }
+            let _ = writeln!(out, "## {} ({})\n", name, musics.len());
+            for music in musics {
+                let _ = writeln!(out, "- {}", markdown_music(music));
+            }
+            out.push('\n');
+        }
+        if !self.changed.is_empty() {
+            let _ = writeln!(out, "## Changed ({})\n", self.changed.len());
+            for change in &self.changed {
+                let _ = writeln!(
+                    out,
+                    "- {}: {}",
+                    markdown_music(&change.after),
+                    change.fields.join(", ")
+                );
+            }
+            out.push('\n');
+        }
+        if self.is_empty() {
+            out.push_str("No changes\n");
+        }
+        out
+    }
+}
+
+/// Music as a markdown list item, linked to its URL when known
+fn markdown_music(music: &Music) -> String {
+    match &music.url {
+        Some(url) => format!("[{}]({}) by {}", music.title, url, music.author),
+        None => format!("**{}** by {}", music.title, music.author),
+    }
+}
+
+/// Musics kept by the filter, without the duplicates, in their order
+fn unique_in_order(musics: &[Music], keep: impl Fn(&Music) -> bool) -> Vec<Music> {
+    let mut seen = HashSet::new();
+    musics
+        .iter()
+        .filter(|music| keep(music) && seen.insert(music.key()))
+        .cloned()
+        .collect()
+}
+
+/// Names of the metadata fields that differ between two occurrences of a music
+fn changed_fields(before: &Music, after: &Music) -> Vec<&'static str> {
+    [
+        ("author", before.author != after.author),
+        ("title", before.title != after.title),
+        ("url", before.url != after.url),
+        ("thumbnail", before.thumbnail != after.thumbnail),
+        ("date", before.date != after.date),
+        ("album", before.album != after.album),
+    ]
+    .into_iter()
+    .filter_map(|(name, changed)| changed.then_some(name))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Music with the given title and album
+    fn music(title: &str, album: Option<&str>) -> Music {
+        Music {
+            author: "Daft Punk".to_string(),
+            title: title.to_string(),
+            album: album.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    /// Test the diff of two lists and its markdown output
+    /// # Panics
+    /// Panics if the diff is not the expected one
+    #[test]
+    fn test_music_diff() {
+        let old = vec![
+            music("One More Time", None),
+            music("Aerodynamic", None),
+            music("Digital Love", Some("Discovery")),
+        ];
+        let new = vec![
+            music("one more time ", Some("Discovery")),
+            music("Digital Love", Some("Discovery")),
+            music("Harder, Better, Faster, Stronger", None),
+        ];
+        let diff = MusicDiff::new(&old, &new);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.removed[0].title, "Aerodynamic");
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].fields, vec!["title", "album"]);
+        assert!(MusicDiff::new(&old, &old).is_empty());
+
+        let markdown = diff.render(DiffFormat::Markdown).unwrap();
+        assert!(markdown.contains("## Added (1)"));
+        assert!(markdown.contains("- **Aerodynamic** by Daft Punk"));
+        assert!(markdown.contains("- **one more time ** by Daft Punk: title, album"));
+    }
+}
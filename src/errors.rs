@@ -2,33 +2,79 @@
 
 use std::sync::{Arc, MutexGuard, PoisonError};
 
-use crate::oauth::ReceivedCode;
+use crate::{oauth::ReceivedCode, PlatformType};
+
+/// Kind of a [`MusicExporterError`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Unclassified error
+    Other,
+
+    /// Invalid configuration (env file, arguments)
+    Config,
+
+    /// Authentication with a platform failed
+    Auth {
+        /// Platform concerned
+        platform: Option<PlatformType>,
+    },
+
+    /// HTTP request failed or returned an error status
+    Http {
+        /// Platform concerned
+        platform: Option<PlatformType>,
+        /// HTTP status of the response
+        status: Option<u16>,
+        /// Body of the response
+        body: Option<String>,
+    },
+
+    /// Failed to parse some data
+    Parse,
+
+    /// IO error
+    Io,
+
+    /// Cancelled by the user
+    Cancelled,
+
+    /// Some platforms failed, the others were saved
+    PartialFailure,
+}
 
 /// Galion error wrapper
 #[derive(Debug)]
 pub struct MusicExporterError {
     /// error message
     pub message: String,
+    /// error kind
+    kind: ErrorKind,
     /// source error
     source: Option<Arc<dyn std::error::Error + Send + Sync>>,
 }
 
-impl std::error::Error for MusicExporterError {}
+impl std::error::Error for MusicExporterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.source {
+            Some(src) => Some(src.as_ref()),
+            None => None,
+        }
+    }
+}
 
 impl Clone for MusicExporterError {
     fn clone(&self) -> Self {
         Self {
             message: self.message.clone(),
+            kind: self.kind.clone(),
             source: self.source.clone(),
         }
     }
 }
 impl std::fmt::Display for MusicExporterError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.source {
-            Some(src) => write!(f, "{} - caused by: {}", self.message, src),
-            None => write!(f, "{}", self.message),
-        }
+        write!(f, "{}", self.message)
     }
 }
 
@@ -39,18 +85,100 @@ impl MusicExporterError {
         let message = ref_str.to_string();
         Self {
             message,
+            kind: ErrorKind::Other,
             source: None,
         }
     }
 
     /// New error with source
+    ///
+    /// The kind of `src` is kept and `src` becomes the cause of the new error
     pub fn new_with_source<S: AsRef<str>>(s: S, src: MusicExporterError) -> Self {
         let ref_str = s.as_ref();
         let message = ref_str.to_string();
         Self {
             message,
-            source: src.source,
+            kind: src.kind.clone(),
+            source: Some(Arc::new(src)),
+        }
+    }
+
+    /// Set the kind of the error
+    pub fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Attach the platform to the error, if the kind has none yet
+    pub fn with_platform(mut self, platform: PlatformType) -> Self {
+        match &mut self.kind {
+            ErrorKind::Auth { platform: p } | ErrorKind::Http { platform: p, .. }
+                if p.is_none() =>
+            {
+                *p = Some(platform);
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// Error from an API response of a platform
+    pub fn from_response(platform: PlatformType, status: u16, body: String) -> Self {
+        let value = serde_json::from_str::<serde_json::Value>(&body).ok();
+        let is_auth = status == 401
+            || value
+                .as_ref()
+                .and_then(|v| v.pointer("/error/type"))
+                .is_some_and(|t| t == "OAuthException");
+        let description = match value {
+            Some(value) => Self::from(value).message,
+            None if body.is_empty() => "empty response".to_string(),
+            None => body.clone(),
+        };
+        let kind = if is_auth {
+            ErrorKind::Auth {
+                platform: Some(platform),
+            }
+        } else {
+            ErrorKind::Http {
+                platform: Some(platform),
+                status: Some(status),
+                body: Some(body),
+            }
+        };
+        Self::new(format!(
+            "{} API error ({}): {}",
+            platform, status, description
+        ))
+        .with_kind(kind)
+    }
+
+    /// Kind of the error
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Multi-line report of the error and all its causes
+    pub fn report(&self) -> String {
+        use std::error::Error;
+        let mut report = self.message.clone();
+        let mut last = self.message.clone();
+        let mut current = self.source();
+        let mut idx = 0;
+        while let Some(cause) = current {
+            let cause_str = cause.to_string();
+            // errors converted with `From` repeat the message of their source
+            if cause_str != last {
+                if idx == 0 {
+                    report.push_str("\nCaused by:");
+                }
+                report.push_str(&format!("\n    {}: {}", idx, cause_str));
+                idx += 1;
+            }
+            last = cause_str;
+            current = cause.source();
         }
+        report
     }
 }
 
@@ -70,6 +198,7 @@ impl From<std::io::Error> for MusicExporterError {
     fn from(error: std::io::Error) -> Self {
         Self {
             message: error.to_string(),
+            kind: ErrorKind::Io,
             source: Some(Arc::new(error)),
         }
     }
@@ -79,6 +208,7 @@ impl From<std::num::ParseIntError> for MusicExporterError {
     fn from(error: std::num::ParseIntError) -> Self {
         Self {
             message: error.to_string(),
+            kind: ErrorKind::Parse,
             source: Some(Arc::new(error)),
         }
     }
@@ -88,6 +218,7 @@ impl From<url::ParseError> for MusicExporterError {
     fn from(error: url::ParseError) -> Self {
         Self {
             message: error.to_string(),
+            kind: ErrorKind::Parse,
             source: Some(Arc::new(error)),
         }
     }
@@ -95,8 +226,18 @@ impl From<url::ParseError> for MusicExporterError {
 
 impl From<reqwest::Error> for MusicExporterError {
     fn from(error: reqwest::Error) -> Self {
+        let kind = if error.is_decode() {
+            ErrorKind::Parse
+        } else {
+            ErrorKind::Http {
+                platform: None,
+                status: error.status().map(|status| status.as_u16()),
+                body: None,
+            }
+        };
         Self {
             message: error.to_string(),
+            kind,
             source: Some(Arc::new(error)),
         }
     }
@@ -106,6 +247,7 @@ impl From<hyper::http::Error> for MusicExporterError {
     fn from(error: hyper::http::Error) -> Self {
         Self {
             message: error.to_string(),
+            kind: ErrorKind::Other,
             source: Some(Arc::new(error)),
         }
     }
@@ -115,6 +257,18 @@ impl From<dotenv::Error> for MusicExporterError {
     fn from(error: dotenv::Error) -> Self {
         Self {
             message: error.to_string(),
+            kind: ErrorKind::Config,
+            source: Some(Arc::new(error)),
+        }
+    }
+}
+
+#[cfg(feature = "history")]
+impl From<rusqlite::Error> for MusicExporterError {
+    fn from(error: rusqlite::Error) -> Self {
+        Self {
+            message: error.to_string(),
+            kind: ErrorKind::Io,
             source: Some(Arc::new(error)),
         }
     }
@@ -135,6 +289,7 @@ impl
     ) -> Self {
         Self {
             message: error.to_string(),
+            kind: ErrorKind::Other,
             source: None,
         }
     }
@@ -142,18 +297,114 @@ impl
 
 impl From<serde_json::Error> for MusicExporterError {
     fn from(error: serde_json::Error) -> Self {
+        let kind = match error.classify() {
+            serde_json::error::Category::Io => ErrorKind::Io,
+            _ => ErrorKind::Parse,
+        };
         Self {
             message: error.to_string(),
+            kind,
             source: Some(Arc::new(error)),
         }
     }
 }
 
+/// Hint to help the user fix a known API error
+fn api_error_hint(reason: &str) -> Option<&'static str> {
+    match reason {
+        "quotaExceeded" | "dailyLimitExceeded" | "rateLimitExceeded" => {
+            Some("the API quota is exhausted, retry later or use another API key")
+        }
+        "OAuthException" | "invalid_grant" | "invalid_token" | "authError" => {
+            Some("the token is invalid or expired, authorize again")
+        }
+        "invalid_client" | "keyInvalid" => Some("check the client id, secret or API key"),
+        _ => None,
+    }
+}
+
 impl From<serde_json::Value> for MusicExporterError {
     fn from(value: serde_json::Value) -> Self {
-        match value.get("error") {
-            Some(serde_json::Value::String(error_message)) => Self::new(error_message.clone()),
-            _ => Self::new(value.to_string()),
+        let (reason, message) = match value.get("error") {
+            // OAuth2 errors: {"error": "invalid_grant", "error_description": "..."}
+            Some(serde_json::Value::String(error)) => (
+                Some(error.clone()),
+                value
+                    .get("error_description")
+                    .and_then(|d| d.as_str())
+                    .map(str::to_string),
+            ),
+            // Spotify: {"error": {"status": 401, "message": "..."}}
+            // Google: {"error": {"code": 403, "message": "...", "errors": [{"reason": "..."}]}}
+            // Deezer: {"error": {"type": "OAuthException", "message": "...", "code": 300}}
+            Some(error @ serde_json::Value::Object(_)) => (
+                error
+                    .get("type")
+                    .or_else(|| error.pointer("/errors/0/reason"))
+                    .and_then(|r| r.as_str())
+                    .map(str::to_string),
+                error
+                    .get("message")
+                    .and_then(|m| m.as_str())
+                    .map(str::to_string),
+            ),
+            _ => (None, None),
+        };
+        let description = match (&reason, message) {
+            (Some(reason), Some(message)) => format!("{}: {}", reason, message),
+            (Some(reason), None) => reason.clone(),
+            (None, Some(message)) => message,
+            (None, None) => value.to_string(),
+        };
+        match reason.as_deref().and_then(api_error_hint) {
+            Some(hint) => Self::new(format!("{} ({})", description, hint)),
+            None => Self::new(description),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the description of the API error payloads
+    /// # Panics
+    /// If the assertion fails
+    #[test]
+    fn test_api_errors() {
+        let deezer = r#"{"error":{"type":"OAuthException","message":"Invalid OAuth access token.","code":300}}"#;
+        let err = MusicExporterError::from_response(PlatformType::Deezer, 200, deezer.to_string());
+        assert!(err
+            .message
+            .contains("OAuthException: Invalid OAuth access token."));
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::Auth {
+                platform: Some(PlatformType::Deezer)
+            }
+        );
+
+        let google = r#"{"error":{"code":403,"message":"The request cannot be completed because you have exceeded your quota.","errors":[{"reason":"quotaExceeded"}]}}"#;
+        let err = MusicExporterError::from_response(PlatformType::Youtube, 403, google.to_string());
+        assert!(err
+            .message
+            .contains("quotaExceeded: The request cannot be completed"));
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::Http {
+                status: Some(403),
+                ..
+            }
+        ));
+
+        let spotify = r#"{"error":{"status":401,"message":"The access token expired"}}"#;
+        let err =
+            MusicExporterError::from_response(PlatformType::Spotify, 401, spotify.to_string());
+        assert!(err.message.ends_with("The access token expired"));
+
+        let oauth =
+            serde_json::json!({"error": "invalid_grant", "error_description": "Bad Request"});
+        let err = MusicExporterError::from(oauth);
+        assert!(err.message.starts_with("invalid_grant: Bad Request"));
+    }
+}
@@ -0,0 +1,260 @@
+//! History of the runs, stored in a SQLite database
+
+use std::path::Path;
+
+use clap::Subcommand;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{
+    errors::{ErrorKind, MusicExporterError},
+    Music, PlatformType,
+};
+
+/// Tables of the history database
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    started_at INTEGER NOT NULL,
+    platforms TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tracks (
+    id INTEGER PRIMARY KEY,
+    title_key TEXT NOT NULL,
+    author_key TEXT NOT NULL,
+    UNIQUE (title_key, author_key)
+);
+CREATE TABLE IF NOT EXISTS run_tracks (
+    run_id INTEGER NOT NULL REFERENCES runs (id),
+    track_id INTEGER NOT NULL REFERENCES tracks (id),
+    music TEXT NOT NULL,
+    PRIMARY KEY (run_id, track_id)
+);
+";
+
+/// Queries of the history database
+#[derive(Debug, Clone, Subcommand)]
+#[non_exhaustive]
+pub enum HistoryQuery {
+    /// Print the musics of the library as of a date (YYYY-MM-DD) or a time (RFC 3339)
+    AsOf {
+        /// Date or time of the library
+        date: String,
+    },
+
+    /// Print the number of musics added each month
+    AddedPerMonth,
+}
+
+impl HistoryQuery {
+    /// Run the query and format its result
+    /// # Errors
+    /// Error if the date is invalid or the query fails
+    pub fn run(&self, history: &History) -> Result<String, MusicExporterError> {
+        match self {
+            HistoryQuery::AsOf { date } => {
+                let musics = history.library_as_of(parse_date(date)?)?;
+                Ok(serde_json::to_string_pretty(&musics)? + "\n")
+            }
+            HistoryQuery::AddedPerMonth => Ok(history
+                .added_per_month()?
+                .into_iter()
+                .map(|(month, count)| format!("{} {}\n", month, count))
+                .collect()),
+        }
+    }
+}
+
+/// Parse a date (the end of the day is used) or a time
+/// # Errors
+/// Config error if the date is invalid
+fn parse_date(date: &str) -> Result<jiff::Timestamp, MusicExporterError> {
+    let invalid = |e: jiff::Error| {
+        MusicExporterError::new(format!(
+            "Invalid date {} (expected YYYY-MM-DD or RFC 3339): {}",
+            date, e
+        ))
+        .with_kind(ErrorKind::Config)
+    };
+    if let Ok(timestamp) = date.parse::<jiff::Timestamp>() {
+        return Ok(timestamp);
+    }
+    let day = date.parse::<jiff::civil::Date>().map_err(invalid)?;
+    Ok(day
+        .at(23, 59, 59, 999_999_999)
+        .to_zoned(jiff::tz::TimeZone::UTC)
+        .map_err(invalid)?
+        .timestamp())
+}
+
+/// History database, recording the musics of every run
+#[derive(Debug)]
+pub struct History {
+    /// Connection to the database
+    conn: Connection,
+}
+
+impl History {
+    /// Open (or create) the history database
+    /// # Errors
+    /// Error if the database cannot be opened
+    pub fn open(path: &Path) -> Result<Self, MusicExporterError> {
+        let conn = Connection::open(path).map_err(|e| {
+            MusicExporterError::new_with_source(
+                format!("Cannot open history database {}", path.display()),
+                e.into(),
+            )
+        })?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// Record the musics of the library after a run, returning the id of the run
+    /// # Errors
+    /// Error if the run cannot be written
+    pub fn record_run(
+        &mut self,
+        at: jiff::Timestamp,
+        platforms: &[PlatformType],
+        musics: &[Music],
+    ) -> Result<i64, MusicExporterError> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO runs (started_at, platforms) VALUES (?1, ?2)",
+            params![at.as_second(), serde_json::to_string(platforms)?],
+        )?;
+        let run_id = tx.last_insert_rowid();
+        {
+            let mut upsert = tx.prepare(
+                "INSERT INTO tracks (title_key, author_key) VALUES (?1, ?2)
+                 ON CONFLICT (title_key, author_key) DO UPDATE SET title_key = title_key
+                 RETURNING id",
+            )?;
+            let mut member = tx.prepare(
+                "INSERT OR IGNORE INTO run_tracks (run_id, track_id, music) VALUES (?1, ?2, ?3)",
+            )?;
+            for music in musics {
+                let (title_key, author_key) = music.key();
+                let track_id: i64 =
+                    upsert.query_row(params![title_key, author_key], |row| row.get(0))?;
+                member.execute(params![run_id, track_id, serde_json::to_string(music)?])?;
+            }
+        }
+        tx.commit()?;
+        log::info!("Run {} recorded in the history", run_id);
+        Ok(run_id)
+    }
+
+    /// Musics of the library at the given time, as recorded by the last run before it
+    ///
+    /// The musics removed from all their platforms are not part of the library
+    /// # Errors
+    /// Error if the query fails
+    pub fn library_as_of(&self, at: jiff::Timestamp) -> Result<Vec<Music>, MusicExporterError> {
+        let run_id: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT id FROM runs WHERE started_at <= ?1 ORDER BY started_at DESC, id DESC",
+                params![at.as_second()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(run_id) = run_id else {
+            return Ok(vec![]);
+        };
+        let mut stmt = self
+            .conn
+            .prepare("SELECT music FROM run_tracks WHERE run_id = ?1 ORDER BY track_id")?;
+        let rows = stmt.query_map(params![run_id], |row| row.get::<_, String>(0))?;
+        let mut musics = vec![];
+        for row in rows {
+            let music: Music = serde_json::from_str(&row?)?;
+            let removed = !music.sources.is_empty()
+                && music
+                    .sources
+                    .values()
+                    .all(|source| source.removed.is_some());
+            if !removed {
+                musics.push(music);
+            }
+        }
+        Ok(musics)
+    }
+
+    /// Number of musics added each month (`YYYY-MM`), from the first run they appear in
+    /// # Errors
+    /// Error if the query fails
+    pub fn added_per_month(&self) -> Result<Vec<(String, u64)>, MusicExporterError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT month, COUNT(*) FROM (
+                SELECT strftime('%Y-%m', MIN(runs.started_at), 'unixepoch') AS month
+                FROM run_tracks JOIN runs ON runs.id = run_tracks.run_id
+                GROUP BY run_tracks.track_id
+             ) GROUP BY month ORDER BY month",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Music with the given title
+    fn music(title: &str) -> Music {
+        Music {
+            author: "Daft Punk".to_string(),
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Test recording runs and querying the history
+    /// # Panics
+    /// Panics if the history is not the expected one
+    #[test]
+    fn test_history() {
+        let mut history = History::open(Path::new(":memory:")).unwrap();
+        let platforms = [PlatformType::Deezer];
+        let first = vec![music("One More Time"), music("Aerodynamic")];
+        let removed = Music {
+            album: Some("Discovery".to_string()),
+            sources: [(
+                PlatformType::Deezer,
+                crate::music::TrackSource {
+                    removed: Some("2024-03-02T00:00:00Z".to_string()),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..music("Aerodynamic")
+        };
+        let second = vec![music("One More Time"), music("Digital Love"), removed];
+        history
+            .record_run(parse_date("2024-01-15").unwrap(), &platforms, &first)
+            .unwrap();
+        history
+            .record_run(parse_date("2024-03-02").unwrap(), &platforms, &second)
+            .unwrap();
+
+        assert!(history
+            .library_as_of(parse_date("2023-12-31").unwrap())
+            .unwrap()
+            .is_empty());
+        let library = history
+            .library_as_of(parse_date("2024-02-01").unwrap())
+            .unwrap();
+        assert_eq!(library, first);
+        assert_eq!(library[1].album, None, "the metadata of that date is kept");
+        let library = history
+            .library_as_of(parse_date("2024-03-02").unwrap())
+            .unwrap();
+        let titles = library.iter().map(|m| m.title.as_str()).collect::<Vec<_>>();
+        assert_eq!(titles, vec!["One More Time", "Digital Love"]);
+        assert_eq!(
+            history.added_per_month().unwrap(),
+            vec![("2024-01".to_string(), 2), ("2024-03".to_string(), 1)]
+        );
+        assert!(parse_date("yesterday").is_err());
+    }
+}
@@ -0,0 +1,299 @@
+//! HTTP helpers shared by the platforms
+
+use reqwest::{header::HeaderMap, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    errors::{ErrorKind, MusicExporterError},
+    PlatformType,
+};
+
+/// HTTP client shared by the platforms
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HttpClient {
+    /// Configured client (connections are reused between requests)
+    client: reqwest::Client,
+
+    /// Retry policy of the requests
+    retry: RetryPolicy,
+}
+
+impl HttpClient {
+    /// Create a new client
+    pub(crate) fn new(client: reqwest::Client, retry: RetryPolicy) -> Self {
+        Self { client, retry }
+    }
+
+    /// Start a GET request
+    pub(crate) fn get<U: reqwest::IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    /// Start a POST request
+    pub(crate) fn post<U: reqwest::IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    /// Send a request and read its JSON body, retrying on transient failures
+    /// # Errors
+    /// Error if the request still fails after the retries
+    pub(crate) async fn fetch_json<T: DeserializeOwned>(
+        &self,
+        platform: PlatformType,
+        request: RequestBuilder,
+    ) -> Result<T, MusicExporterError> {
+        fetch_json(platform, &self.retry, request).await
+    }
+}
+
+/// Retry policy of the platform requests
+#[derive(Debug, Clone)]
+pub(crate) struct RetryPolicy {
+    /// Maximum number of retries of a request
+    pub max_retries: u32,
+
+    /// Base delay of the exponential backoff
+    pub base_delay: Duration,
+
+    /// Maximum delay between two attempts
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(1000),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay to wait before the retry number `attempt` (starting at 0)
+    ///
+    /// The `Retry-After` delay asked by the server is honored up to the
+    /// maximum delay, otherwise an exponential backoff with jitter is used
+    fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        // jitter between 50% and 100% of the delay
+        let jitter = random_u64() % 1000;
+        exp / 2 + exp.mul_f64(jitter as f64 / 2000.0)
+    }
+}
+
+/// Random number without adding a dependency
+fn random_u64() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    hasher.finish()
+}
+
+/// Parse the `Retry-After` header (delay in seconds or HTTP date)
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(secs) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+/// Check if a failed response is worth retrying
+fn is_retryable(status: StatusCode, error: &MusicExporterError) -> bool {
+    if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        return true;
+    }
+    let body = match error.kind() {
+        ErrorKind::Http {
+            body: Some(body), ..
+        } => body,
+        _ => return false,
+    };
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(body) else {
+        return false;
+    };
+    // Deezer "Quota limit exceeded"
+    let deezer_quota = value.pointer("/error/code").and_then(|c| c.as_u64()) == Some(4);
+    // Google short-term rate limits (the daily "quotaExceeded" is not retried)
+    let google_rate_limit = value
+        .pointer("/error/errors/0/reason")
+        .and_then(|r| r.as_str())
+        .is_some_and(|r| r == "rateLimitExceeded" || r == "userRateLimitExceeded");
+    deezer_quota || google_rate_limit
+}
+
+/// Send a request and read its JSON body, retrying on transient failures
+/// # Errors
+/// Error if the request still fails after the retries
+async fn fetch_json<T: DeserializeOwned>(
+    platform: PlatformType,
+    policy: &RetryPolicy,
+    request: RequestBuilder,
+) -> Result<T, MusicExporterError> {
+    let mut attempt = 0;
+    loop {
+        let req = request
+            .try_clone()
+            .ok_or(MusicExporterError::new("Cannot clone the request"))?;
+        let (error, retry_after) = match req.send().await {
+            Ok(resp) => {
+                let status = resp.status();
+                let retry_after = parse_retry_after(resp.headers());
+                match read_json(platform, resp).await {
+                    Err(e) if is_retryable(status, &e) => (e, retry_after),
+                    res => return res,
+                }
+            }
+            Err(e) if e.is_timeout() || e.is_connect() || e.is_request() => (e.into(), None),
+            Err(e) => return Err(e.into()),
+        };
+        if attempt >= policy.max_retries {
+            return Err(MusicExporterError::new_with_source(
+                format!("Request failed after {} retries", attempt),
+                error,
+            ));
+        }
+        let delay = policy.delay(attempt, retry_after);
+        log::warn!(
+            "{}: {} - retrying in {:.1}s ({}/{})",
+            platform,
+            error,
+            delay.as_secs_f64(),
+            attempt + 1,
+            policy.max_retries
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Read the JSON body of a platform response
+///
+/// Error payloads are detected even when the status is a success,
+/// since Deezer answers with `200 OK` and an `error` object
+/// # Errors
+/// Error if the platform returned an error or if the body is not the expected json
+pub(crate) async fn read_json<T: DeserializeOwned>(
+    platform: PlatformType,
+    resp: reqwest::Response,
+) -> Result<T, MusicExporterError> {
+    let status = resp.status();
+    let body = resp.text().await?;
+    match serde_json::from_str::<serde_json::Value>(&body) {
+        Ok(value) if status.is_success() && value.get("error").is_none() => {
+            Ok(serde_json::from_value(value)?)
+        }
+        Ok(_) => Err(MusicExporterError::from_response(
+            platform,
+            status.as_u16(),
+            body,
+        )),
+        Err(e) if status.is_success() => Err(e.into()),
+        Err(_) => Err(MusicExporterError::from_response(
+            platform,
+            status.as_u16(),
+            body,
+        )),
+    }
+}
+
+/// Read the JSON body of a response of an OAuth token endpoint
+///
+/// Requests rejected with 400 or 401 (invalid code, client or secret) are
+/// authentication errors, the other failures keep their kind
+/// # Errors
+/// Error if the platform returned an error or if the body is not the expected json
+pub(crate) async fn read_token<T: DeserializeOwned>(
+    platform: PlatformType,
+    resp: reqwest::Response,
+) -> Result<T, MusicExporterError> {
+    read_json(platform, resp).await.map_err(|e| {
+        let error = MusicExporterError::new_with_source("Failed to get the access token", e);
+        match error.kind() {
+            ErrorKind::Http {
+                status: Some(400 | 401),
+                ..
+            } => error.with_kind(ErrorKind::Auth {
+                platform: Some(platform),
+            }),
+            _ => error,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the backoff delays
+    /// # Panics
+    /// If the assertion fails
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy::default();
+        for attempt in 0..10 {
+            let delay = policy.delay(attempt, None);
+            let exp = (policy.base_delay * 2u32.pow(attempt)).min(policy.max_delay);
+            assert!(delay >= exp / 2 && delay <= exp);
+        }
+        let retry_after = Some(Duration::from_secs(7));
+        assert_eq!(policy.delay(0, retry_after), Duration::from_secs(7));
+        let retry_after = Some(Duration::from_secs(86400));
+        assert_eq!(policy.delay(0, retry_after), policy.max_delay);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(reqwest::header::RETRY_AFTER, "120".parse().unwrap());
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(120)));
+    }
+
+    /// Test the kinds of the errors of the token endpoints
+    /// # Panics
+    /// If the assertion fails
+    #[tokio::test]
+    async fn test_read_token() {
+        let response = |status: u16, body: &str| {
+            reqwest::Response::from(
+                hyper::http::Response::builder()
+                    .status(status)
+                    .body(body.to_string())
+                    .unwrap(),
+            )
+        };
+        let token = |status, body| {
+            read_token::<serde_json::Value>(PlatformType::Spotify, response(status, body))
+        };
+        let err = token(400, r#"{"error":"invalid_grant"}"#)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::Auth {
+                platform: Some(PlatformType::Spotify)
+            }
+        );
+        let err = token(503, "Service Unavailable").await.unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::Http {
+                status: Some(503),
+                ..
+            }
+        ));
+        let err = token(200, "not json").await.unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Parse);
+    }
+}
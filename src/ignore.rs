@@ -0,0 +1,202 @@
+//! Musics ignored by the export
+
+use std::{collections::HashSet, fs, path::Path, str::FromStr};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    errors::{ErrorKind, MusicExporterError},
+    normalize::normalize,
+    Music,
+};
+
+/// Content of an ignore file
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+struct IgnoreFile {
+    /// URLs of the ignored musics
+    #[serde(default)]
+    urls: Vec<String>,
+
+    /// Platform ids of the ignored musics
+    #[serde(default)]
+    platform_ids: Vec<String>,
+
+    /// Ignored artists
+    #[serde(default)]
+    artists: Vec<String>,
+
+    /// Ignored Youtube channels
+    #[serde(default)]
+    channels: Vec<String>,
+
+    /// Regexes (case insensitive) of the ignored titles
+    #[serde(default)]
+    titles: Vec<String>,
+}
+
+/// Musics ignored by the export
+#[derive(Debug, Default, Clone)]
+pub struct IgnoreList {
+    /// URLs of the ignored musics
+    urls: HashSet<String>,
+
+    /// Platform ids of the ignored musics
+    platform_ids: HashSet<String>,
+
+    /// Normalized ignored artists
+    artists: HashSet<String>,
+
+    /// Normalized ignored channels
+    channels: HashSet<String>,
+
+    /// Regexes of the ignored titles
+    titles: Vec<Regex>,
+}
+
+impl IgnoreList {
+    /// Load a JSON ignore file
+    /// # Errors
+    /// Config error if the file cannot be read or a regex is invalid
+    pub fn from_file(path: &Path) -> Result<Self, MusicExporterError> {
+        fs::read_to_string(path)
+            .map_err(MusicExporterError::from)
+            .and_then(|content| content.parse())
+            .map_err(|e| {
+                MusicExporterError::new_with_source(
+                    format!("Cannot load the ignore file {}", path.display()),
+                    e,
+                )
+                .with_kind(ErrorKind::Config)
+            })
+    }
+
+    /// Whether a music is ignored
+    ///
+    /// The channel is the original author of a Youtube video, and the original
+    /// title is also matched
+    pub fn is_ignored(&self, music: &Music) -> bool {
+        music
+            .url
+            .as_ref()
+            .is_some_and(|url| self.urls.contains(url))
+            || music
+                .platform_id()
+                .is_some_and(|id| self.platform_ids.contains(&id))
+            || self.artists.contains(&normalize(&music.author))
+            || music
+                .original_author
+                .as_ref()
+                .is_some_and(|channel| self.channels.contains(&normalize(channel)))
+            || self.channels.contains(&normalize(&music.author))
+            || self.titles.iter().any(|regex| {
+                regex.is_match(&music.title)
+                    || music
+                        .original_title
+                        .as_ref()
+                        .is_some_and(|title| regex.is_match(title))
+            })
+    }
+
+    /// Remove the ignored musics, returning how many were removed
+    pub fn filter(&self, musics: &mut Vec<Music>) -> usize {
+        let count = musics.len();
+        musics.retain(|music| {
+            let ignored = self.is_ignored(music);
+            if ignored {
+                log::debug!("Ignored: {} by {}", music.title, music.author);
+            }
+            !ignored
+        });
+        count - musics.len()
+    }
+}
+
+impl FromStr for IgnoreList {
+    type Err = MusicExporterError;
+
+    fn from_str(content: &str) -> Result<Self, Self::Err> {
+        let file = serde_json::from_str::<IgnoreFile>(content)?;
+        let titles = file
+            .titles
+            .iter()
+            .map(|title| {
+                RegexBuilder::new(title)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| {
+                        MusicExporterError::new(format!("Invalid ignored title {}: {}", title, e))
+                            .with_kind(ErrorKind::Config)
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            urls: file.urls.into_iter().collect(),
+            platform_ids: file.platform_ids.into_iter().collect(),
+            artists: file
+                .artists
+                .iter()
+                .map(|artist| normalize(artist))
+                .collect(),
+            channels: file
+                .channels
+                .iter()
+                .map(|channel| normalize(channel))
+                .collect(),
+            titles,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the ignored musics
+    /// # Panics
+    /// If the assertion fails
+    #[test]
+    fn test_ignore_list() {
+        let ignore = r#"{
+                "urls": ["https://www.deezer.com/track/1"],
+                "platform_ids": ["abc"],
+                "artists": ["Some Podcast"],
+                "channels": ["Tutorials TV"],
+                "titles": ["\\bpodcast\\b", "^how to "]
+            }"#
+        .parse::<IgnoreList>()
+        .unwrap();
+        let music = |title: &str, author: &str, url: Option<&str>| Music {
+            title: title.to_string(),
+            author: author.to_string(),
+            url: url.map(str::to_string),
+            ..Default::default()
+        };
+        let mut musics = vec![
+            music("Song", "Artist", Some("https://www.deezer.com/track/1")),
+            music(
+                "Song",
+                "Artist",
+                Some("https://www.youtube.com/watch?v=abc"),
+            ),
+            music("Episode 12", "some podcast", None),
+            music("How to cook", "Chef", None),
+            music("My Podcast Ep. 3", "Host", None),
+            Music {
+                original_author: Some("Tutorials TV".to_string()),
+                ..music("Rust", "Someone", None)
+            },
+            music("Song", "Artist", Some("https://www.deezer.com/track/2")),
+        ];
+        assert_eq!(ignore.filter(&mut musics), 6);
+        assert_eq!(musics.len(), 1);
+        assert_eq!(
+            musics[0].url.as_deref(),
+            Some("https://www.deezer.com/track/2")
+        );
+
+        let err = r#"{ "titles": ["("] }"#.parse::<IgnoreList>().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Config);
+        let err = r#"{ "titles": "#.parse::<IgnoreList>().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Parse);
+    }
+}
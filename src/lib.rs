@@ -31,9 +31,21 @@
 )]
 #![warn(clippy::multiple_crate_versions)]
 
+pub(crate) mod diff;
+#[cfg(feature = "history")]
+pub(crate) mod history;
+pub(crate) mod http;
+pub(crate) mod ignore;
 mod macros;
 pub(crate) mod music;
+pub(crate) mod normalize;
 pub(crate) mod oauth;
+pub(crate) mod overrides;
+pub(crate) mod pagination;
+pub(crate) mod rules;
+pub(crate) mod schema;
+pub(crate) mod storage;
+pub(crate) mod summary;
 pub(crate) mod utils;
 
 mod deezer;
@@ -42,12 +54,23 @@ mod spotify;
 mod youtube;
 
 pub use deezer::lib::DeezerPlatform;
-pub use music::Music;
+pub use diff::{DiffFormat, MusicChange, MusicDiff};
+#[cfg(feature = "history")]
+pub use history::{History, HistoryQuery};
+pub use ignore::IgnoreList;
+pub use music::{DedupOptions, MergePolicy, Music, TrackSource};
+pub use overrides::{Overrides, TrackOverride};
+pub use pagination::{Cursor, Page, Paginator};
+pub use rules::{default_rules, CleaningRule, CleaningRules, RuleField};
+pub use schema::{MusicFile, SCHEMA_VERSION};
 pub use spotify::lib::SpotifyPlatform;
+pub use summary::{PlatformOutcome, RunSummary};
 pub use youtube::lib::YoutubePlatform;
 
+pub use errors::ErrorKind;
 pub use errors::MusicExporterError;
 pub use utils::music_exporter_main;
+pub use utils::Command;
 pub use utils::MusicExporter;
 pub use utils::Platform;
 pub use utils::PlatformType;
@@ -1,22 +1,51 @@
 use clap::Parser;
 use std::process::exit;
 
-use music_exporter::MusicExporter;
+use music_exporter::{Command, ErrorKind, MusicExporter, MusicFile};
+
+/// Exit code of the CLI for the given error kind
+fn exit_code(kind: &ErrorKind) -> i32 {
+    match kind {
+        ErrorKind::Config => 2,
+        ErrorKind::Auth { .. } => 3,
+        ErrorKind::Http { .. } => 4,
+        ErrorKind::Parse => 5,
+        ErrorKind::Io => 6,
+        ErrorKind::PartialFailure => 7,
+        ErrorKind::Cancelled => 130,
+        _ => 1,
+    }
+}
 
 #[tokio::main]
 async fn main() {
-    println!("music-exporter");
+    let music_exp = MusicExporter::parse();
+    if let Some(Command::Schema) = music_exp.command {
+        println!("{:#}", MusicFile::json_schema());
+        return;
+    }
     env_logger::builder()
         .filter_level(log::LevelFilter::Info)
         .format_target(false)
         .format_timestamp(None)
         .init();
-    let music_exp = MusicExporter::parse();
-    match music_exp.run_main().await {
-        Ok(_) => {}
-        Err(e) => {
-            eprintln!("{}", e);
-            exit(1);
+    let res = match &music_exp.command {
+        Some(Command::Diff { old, new, format }) => music_exp
+            .diff(old.as_deref(), new.as_deref())
+            .await
+            .and_then(|diff| Ok(diff.render(*format)?))
+            .map(|output| print!("{}", output)),
+        #[cfg(feature = "history")]
+        Some(Command::History { query }) => music_exp
+            .query_history(query)
+            .map(|output| print!("{}", output)),
+        _ => {
+            println!("music-exporter");
+            music_exp.run_main().await.map(|_| ())
         }
     };
-}
\ No newline at end of file
+    if let Err(e) = res {
+        eprintln!("Error: {}", e.report());
+        exit(exit_code(e.kind()));
+    }
+}
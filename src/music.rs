@@ -1,7 +1,14 @@
 //! Music struct and utility functions
 
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
+};
+
+use crate::{normalize, PlatformType};
+
 /// Music struct
-#[derive(Debug, Ord, Eq, PartialOrd, serde::Deserialize, serde::Serialize, Clone)]
+#[derive(Debug, Default, Eq, serde::Deserialize, serde::Serialize, Clone)]
 pub struct Music {
     /// Author of the music
     pub author: String,
@@ -20,17 +27,267 @@ pub struct Music {
 
     /// Album of the music
     pub album: Option<String>,
+
+    /// Title given by the platform, if it was changed (like the "Artist - Title" of Youtube)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_title: Option<String>,
+
+    /// Author given by the platform, if it was changed (like the channel of Youtube)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_author: Option<String>,
+
+    /// Title without its version label, if it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_title: Option<String>,
+
+    /// Version label of the title, like "Live" or "2011 Remaster"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// Tags added by the user (never overwritten by the exporter)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+
+    /// Rating given by the user (never overwritten by the exporter)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<u8>,
+
+    /// Notes of the user (never overwritten by the exporter)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+
+    /// First time the music was seen on a platform (RFC 3339)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<String>,
+
+    /// Last time the music was seen on a platform (RFC 3339)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<String>,
+
+    /// Platforms the music was seen on
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sources: BTreeMap<PlatformType, TrackSource>,
+
+    /// Other fields added by the user, kept as they are
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Where a music was seen on a platform
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct TrackSource {
+    /// URL of the music on the platform
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+
+    /// First time the music was seen on the platform (RFC 3339)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<String>,
+
+    /// Last time the music was seen on the platform (RFC 3339)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<String>,
+
+    /// Time the music was found missing from the platform (RFC 3339), if it is no longer there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed: Option<String>,
+}
+
+impl TrackSource {
+    /// Source of a music seen at the given time
+    pub fn seen(url: Option<String>, now: &str) -> Self {
+        Self {
+            url,
+            first_seen: Some(now.to_string()),
+            last_seen: Some(now.to_string()),
+            removed: None,
+        }
+    }
+
+    /// Update the source with a newer sighting of the music
+    fn merge(&mut self, other: TrackSource) {
+        self.first_seen = self.first_seen.take().or(other.first_seen);
+        if other.url.is_some() {
+            self.url = other.url;
+        }
+        if other.last_seen.is_some() {
+            self.last_seen = other.last_seen;
+            self.removed = None;
+        }
+    }
+}
+
+/// How a music fetched from a platform updates the same music already known
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[non_exhaustive]
+pub enum MergePolicy {
+    /// Keep the existing music as it is
+    KeepExisting,
+
+    /// Replace the metadata by the one of the platform of the music URL
+    PreferPlatform,
+
+    /// Only fill the metadata missing in the existing music
+    #[default]
+    FillMissing,
+}
+
+/// Options of the removal of the duplicates
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DedupOptions {
+    /// Minimum similarity of the titles of an author to be duplicates
+    pub similarity: Option<f64>,
+
+    /// Whether the versions of a title (live, remaster...) are duplicates
+    pub merge_versions: bool,
 }
 
 impl Music {
+    /// Fill the base title and the version label from the title
+    pub fn detect_version(&mut self) {
+        if let (base_title, Some(version)) = normalize::split_version(&self.title) {
+            self.base_title = Some(base_title);
+            self.version = Some(version);
+        }
+    }
+
+    /// Detect the base title and the version label again, after the title changed
+    pub fn refresh_version(&mut self) {
+        self.base_title = None;
+        self.version = None;
+        self.detect_version();
+    }
+
+    /// Identifier of the music on its platform, taken from its URL
+    ///
+    /// This is the `v` parameter for Youtube and the last path segment otherwise
+    pub fn platform_id(&self) -> Option<String> {
+        let url = url::Url::parse(self.url.as_ref()?).ok()?;
+        if let Some((_, id)) = url.query_pairs().find(|(key, _)| key == "v") {
+            return Some(id.to_string());
+        }
+        url.path_segments()?
+            .rfind(|segment| !segment.is_empty())
+            .map(str::to_string)
+    }
+
+    /// Whether the music is still liked on the platform (it was exported from it
+    /// and not removed since)
+    pub fn is_liked_on(&self, platform: PlatformType) -> bool {
+        self.sources
+            .get(&platform)
+            .is_some_and(|source| source.removed.is_none())
+    }
+
+    /// Update the music with another occurrence of it, following the merge policy
+    ///
+    /// The sources are always merged, the user annotations are never overwritten
+    pub fn merge(&mut self, mut other: Music, policy: MergePolicy) {
+        for (platform, source) in std::mem::take(&mut other.sources) {
+            match self.sources.get_mut(&platform) {
+                Some(existing) => existing.merge(source),
+                None => {
+                    self.sources.insert(platform, source);
+                }
+            }
+        }
+        self.first_seen = self.first_seen.take().or(other.first_seen.take());
+        if other.last_seen.is_some() {
+            self.last_seen = other.last_seen.take();
+        }
+        // the same music from another platform does not switch the URL, title and author
+        let policy = match policy {
+            MergePolicy::PreferPlatform if self.url.is_some() && self.url != other.url => {
+                MergePolicy::FillMissing
+            }
+            policy => policy,
+        };
+        let fields = self.metadata_mut().into_iter().zip(other.metadata_mut());
+        match policy {
+            MergePolicy::KeepExisting => {}
+            MergePolicy::PreferPlatform => {
+                for (field, value) in fields {
+                    if value.is_some() {
+                        *field = value.take();
+                    }
+                }
+                self.author = other.author;
+                self.title = other.title;
+                self.original_title = other.original_title;
+                self.original_author = other.original_author;
+                self.base_title = other.base_title;
+                self.version = other.version;
+            }
+            MergePolicy::FillMissing => {
+                for (field, value) in fields {
+                    if field.is_none() {
+                        *field = value.take();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Mutable optional metadata coming from the platforms
+    fn metadata_mut(&mut self) -> [&mut Option<String>; 4] {
+        [
+            &mut self.url,
+            &mut self.thumbnail,
+            &mut self.date,
+            &mut self.album,
+        ]
+    }
+
+    /// Key identifying the music, two musics with the same key are equal
+    pub(crate) fn key(&self) -> (String, String) {
+        (self.normalized_title(), self.normalized_author())
+    }
+
+    /// Key identifying the music, the same for all its versions
+    fn work_key(&self) -> (String, String) {
+        let title = self.base_title.as_ref().unwrap_or(&self.title);
+        (normalize::normalize(title), self.normalized_author())
+    }
+
     /// normalized title
     fn normalized_title(&self) -> String {
-        self.title.trim().to_lowercase()
+        normalize::normalize(&self.title)
     }
 
     /// normalized author
     fn normalized_author(&self) -> String {
-        self.author.trim().to_lowercase()
+        normalize::normalize(&self.author)
+    }
+
+    /// Title and author, as displayed in the logs
+    fn label(&self) -> String {
+        format!("{} by {}", self.title, self.author)
+    }
+}
+
+impl Music {
+    /// Key used to sort the musics
+    fn sort_key(&self) -> impl Ord + '_ {
+        (
+            &self.author,
+            &self.title,
+            &self.url,
+            &self.thumbnail,
+            &self.date,
+            &self.album,
+        )
+    }
+}
+
+impl Ord for Music {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+impl PartialOrd for Music {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -41,24 +298,422 @@ impl PartialEq for Music {
     }
 }
 
-use std::collections::HashSet;
+/// Group of musics merged as duplicates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// Music kept
+    pub kept: String,
+
+    /// Other spellings of the music merged into it
+    pub merged: Vec<String>,
+}
+
+/// Musics without duplicates, each duplicate being merged into the first occurrence
+#[derive(Debug, Default)]
+struct Dedup {
+    /// Musics kept
+    musics: Vec<Music>,
+
+    /// Options of the removal
+    options: DedupOptions,
+
+    /// Index of the musics by URL
+    by_url: HashMap<String, usize>,
+
+    /// Index of the musics by normalized title and author
+    by_key: HashMap<(String, String), usize>,
+
+    /// Normalized titles of each normalized author, for the similarity
+    by_author: HashMap<String, Vec<(String, usize)>>,
+
+    /// Other spellings merged into each music
+    variants: BTreeMap<usize, Vec<String>>,
+}
+
+impl Dedup {
+    /// Empty list
+    fn new(options: DedupOptions) -> Self {
+        Self {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Key of a music in the index
+    fn key(&self, music: &Music) -> (String, String) {
+        if self.options.merge_versions {
+            music.work_key()
+        } else {
+            music.key()
+        }
+    }
+
+    /// Find the music a new one is a duplicate of
+    ///
+    /// The URL is tried first, then the normalized title and author, then
+    /// the most similar title of the same author
+    fn find(&self, music: &Music) -> Option<usize> {
+        if let Some(idx) = music.url.as_ref().and_then(|url| self.by_url.get(url)) {
+            return Some(*idx);
+        }
+        let (title, author) = self.key(music);
+        if let Some(idx) = self.by_key.get(&(title.clone(), author.clone())) {
+            return Some(*idx);
+        }
+        let threshold = self.options.similarity?;
+        self.by_author
+            .get(&author)?
+            .iter()
+            .map(|(other, idx)| (normalize::similarity(&title, other), *idx))
+            .filter(|(score, _)| *score >= threshold)
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, idx)| idx)
+    }
+
+    /// Index a music (again, as merging may change it)
+    fn index(&mut self, idx: usize) {
+        let music = &self.musics[idx];
+        if let Some(url) = &music.url {
+            self.by_url.entry(url.clone()).or_insert(idx);
+        }
+        let (title, author) = self.key(music);
+        if let Entry::Vacant(entry) = self.by_key.entry((title.clone(), author.clone())) {
+            entry.insert(idx);
+            self.by_author.entry(author).or_default().push((title, idx));
+        }
+    }
+
+    /// Add a music, returning whether it was merged as a duplicate
+    fn add(&mut self, music: Music, policy: MergePolicy) -> bool {
+        match self.find(&music) {
+            Some(idx) => {
+                log::debug!("Merging: {}", music.label());
+                let label = music.label();
+                if label != self.musics[idx].label() {
+                    self.variants.entry(idx).or_default().push(label);
+                }
+                self.musics[idx].merge(music, policy);
+                self.index(idx);
+                true
+            }
+            None => {
+                self.musics.push(music);
+                self.index(self.musics.len() - 1);
+                false
+            }
+        }
+    }
+
+    /// Groups of musics merged with a different spelling
+    fn groups(&self) -> Vec<DuplicateGroup> {
+        self.variants
+            .iter()
+            .filter_map(|(idx, variants)| {
+                let kept = self.musics[*idx].label();
+                let mut merged = variants.clone();
+                merged.retain(|variant| *variant != kept);
+                merged.sort();
+                merged.dedup();
+                (!merged.is_empty()).then_some(DuplicateGroup { kept, merged })
+            })
+            .collect()
+    }
+
+    /// Log the merged groups and return the musics
+    fn finish(self) -> Vec<Music> {
+        let groups = self.groups();
+        if !groups.is_empty() {
+            log::info!("Merged {} groups of duplicates:", groups.len());
+        }
+        for group in groups {
+            log::info!("  {} <- {}", group.kept, group.merged.join(" | "));
+        }
+        self.musics
+    }
+}
 
 /// Remove duplicates from a vector of Music
-pub fn unique_music(music_vec: Vec<Music>) -> Vec<Music> {
-    let mut unique_vec = Vec::new();
-    let mut seen = HashSet::new();
+pub fn unique_music(music_vec: Vec<Music>, options: DedupOptions) -> Vec<Music> {
+    let mut dedup = Dedup::new(options);
     let mut dup_count = 0;
     for music in music_vec {
-        if seen.insert((
-            music.normalized_title().clone(),
-            music.normalized_author().clone(),
-        )) {
-            unique_vec.push(music);
-        } else {
-            log::debug!("Duplicate: {} by {}", music.title, music.author);
+        if dedup.add(music, MergePolicy::KeepExisting) {
             dup_count += 1;
         }
     }
     log::info!("Duplicates: {}", dup_count);
-    unique_vec
+    dedup.finish()
+}
+
+/// Mark the musics of a platform that were not fetched from it as removed
+///
+/// `fetched` contains the URLs of all the musics of the platform, so it must only
+/// be used after a complete export. Returns the number of newly removed musics
+pub fn mark_removed(
+    musics: &mut [Music],
+    platform: PlatformType,
+    fetched: &HashSet<String>,
+    now: &str,
+) -> usize {
+    let mut removed = 0;
+    for music in musics {
+        let Some(source) = music.sources.get_mut(&platform) else {
+            continue;
+        };
+        let still_there = source.url.as_ref().is_some_and(|url| fetched.contains(url));
+        if !still_there && source.removed.is_none() {
+            log::debug!(
+                "Removed from {}: {} by {}",
+                platform,
+                music.title,
+                music.author
+            );
+            source.removed = Some(now.to_string());
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Upsert the fetched musics into the existing ones, without duplicates
+///
+/// A fetched music updates the existing music with the same URL, or else the same
+/// normalized title and author (or a similar title), following the merge policy;
+/// the others are appended
+pub fn merge_music(
+    existing: Vec<Music>,
+    fetched: Vec<Music>,
+    policy: MergePolicy,
+    options: DedupOptions,
+) -> Vec<Music> {
+    let mut dedup = Dedup::new(options);
+    let mut dup_count = 0;
+    for music in existing {
+        if dedup.add(music, MergePolicy::KeepExisting) {
+            dup_count += 1;
+        }
+    }
+    log::info!("Duplicates: {}", dup_count);
+    let mut updated = 0;
+    for music in fetched {
+        if dedup.add(music, policy) {
+            updated += 1;
+        }
+    }
+    log::info!("Merged: {}", updated);
+    dedup.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Music fetched from Deezer
+    fn fetched(title: &str, album: Option<&str>) -> Music {
+        let url = "https://www.deezer.com/track/1".to_string();
+        Music {
+            author: "Daft Punk".to_string(),
+            title: title.to_string(),
+            url: Some(url.clone()),
+            album: album.map(str::to_string),
+            sources: BTreeMap::from([(PlatformType::Deezer, TrackSource::seen(Some(url), "2"))]),
+            ..Default::default()
+        }
+    }
+
+    /// Test the merge policies
+    /// # Panics
+    /// Panics if the musics are not merged as expected
+    #[test]
+    fn test_merge_music() {
+        let existing = Music {
+            album: Some("Old".to_string()),
+            rating: Some(5),
+            sources: BTreeMap::new(),
+            ..fetched("one more time", None)
+        };
+        let new = || {
+            vec![
+                Music {
+                    thumbnail: Some("thumb".to_string()),
+                    ..fetched("One More Time", Some("Discovery"))
+                },
+                Music {
+                    url: Some("https://open.spotify.com/track/2".to_string()),
+                    sources: BTreeMap::from([(PlatformType::Spotify, TrackSource::default())]),
+                    ..fetched("One more time ", None)
+                },
+            ]
+        };
+
+        let merged = merge_music(
+            vec![existing.clone()],
+            new(),
+            MergePolicy::KeepExisting,
+            DedupOptions::default(),
+        );
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].title, "one more time");
+        assert_eq!(merged[0].thumbnail, None);
+        assert_eq!(merged[0].sources.len(), 2);
+
+        let merged = merge_music(
+            vec![existing.clone()],
+            new(),
+            MergePolicy::FillMissing,
+            DedupOptions::default(),
+        );
+        assert_eq!(merged[0].title, "one more time");
+        assert_eq!(merged[0].album.as_deref(), Some("Old"));
+        assert_eq!(merged[0].thumbnail.as_deref(), Some("thumb"));
+
+        let merged = merge_music(
+            vec![existing],
+            new(),
+            MergePolicy::PreferPlatform,
+            DedupOptions::default(),
+        );
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].title, "One More Time");
+        assert_eq!(
+            merged[0].url.as_deref(),
+            Some("https://www.deezer.com/track/1")
+        );
+        assert_eq!(merged[0].album.as_deref(), Some("Discovery"));
+        assert_eq!(merged[0].thumbnail.as_deref(), Some("thumb"));
+        assert_eq!(merged[0].rating, Some(5));
+    }
+
+    /// Test the first and last seen times, and the removed musics
+    /// # Panics
+    /// Panics if the times are not updated as expected
+    #[test]
+    fn test_seen_and_removed() {
+        let existing = Music {
+            sources: BTreeMap::from([
+                (
+                    PlatformType::Deezer,
+                    TrackSource::seen(Some("https://www.deezer.com/track/1".to_string()), "1"),
+                ),
+                (
+                    PlatformType::Spotify,
+                    TrackSource::seen(Some("https://open.spotify.com/track/2".to_string()), "1"),
+                ),
+            ]),
+            ..fetched("One More Time", None)
+        };
+        let mut merged = merge_music(
+            vec![existing],
+            vec![fetched("One More Time", None)],
+            MergePolicy::FillMissing,
+            DedupOptions::default(),
+        );
+        let deezer = &merged[0].sources[&PlatformType::Deezer];
+        assert_eq!(deezer.first_seen.as_deref(), Some("1"));
+        assert_eq!(deezer.last_seen.as_deref(), Some("2"));
+
+        let fetched_urls = HashSet::from(["https://www.deezer.com/track/1".to_string()]);
+        assert_eq!(
+            mark_removed(&mut merged, PlatformType::Deezer, &fetched_urls, "2"),
+            0
+        );
+        assert_eq!(
+            mark_removed(&mut merged, PlatformType::Spotify, &HashSet::new(), "2"),
+            1
+        );
+        let spotify = &merged[0].sources[&PlatformType::Spotify];
+        assert_eq!(spotify.last_seen.as_deref(), Some("1"));
+        assert_eq!(spotify.removed.as_deref(), Some("2"));
+        assert!(merged[0].is_liked_on(PlatformType::Deezer));
+        assert!(!merged[0].is_liked_on(PlatformType::Spotify));
+        assert!(!merged[0].is_liked_on(PlatformType::Youtube));
+        assert_eq!(
+            mark_removed(&mut merged, PlatformType::Spotify, &HashSet::new(), "3"),
+            0
+        );
+    }
+
+    /// Test the fuzzy duplicates and the report of the merged groups
+    /// # Panics
+    /// Panics if the duplicates are not merged as expected
+    #[test]
+    fn test_fuzzy_duplicates() {
+        let music = |title: &str, author: &str| Music {
+            title: title.to_string(),
+            author: author.to_string(),
+            ..Default::default()
+        };
+        let musics = vec![
+            music("Déjà Vu", "Beyoncé feat. Jay-Z"),
+            music("Deja vu!", "Beyonce"),
+            music("Mrs. Robinson", "Simon & Garfunkel"),
+            music("Mrs Robinsonn", "Simon and Garfunkel"),
+        ];
+        assert_eq!(
+            unique_music(musics.clone(), DedupOptions::default()).len(),
+            3
+        );
+
+        let mut dedup = Dedup::new(DedupOptions {
+            similarity: Some(0.9),
+            ..Default::default()
+        });
+        for music in musics {
+            dedup.add(music, MergePolicy::KeepExisting);
+        }
+        assert_eq!(
+            dedup.groups(),
+            vec![
+                DuplicateGroup {
+                    kept: "Déjà Vu by Beyoncé feat. Jay-Z".to_string(),
+                    merged: vec!["Deja vu! by Beyonce".to_string()],
+                },
+                DuplicateGroup {
+                    kept: "Mrs. Robinson by Simon & Garfunkel".to_string(),
+                    merged: vec!["Mrs Robinsonn by Simon and Garfunkel".to_string()],
+                },
+            ]
+        );
+        assert_eq!(dedup.finish().len(), 2);
+    }
+
+    /// Test keeping or merging the versions of a title
+    /// # Panics
+    /// Panics if the versions are not merged as expected
+    #[test]
+    fn test_versions() {
+        let musics = ["Heroes", "Heroes - 2017 Remaster", "Heroes (Live)"]
+            .into_iter()
+            .map(|title| {
+                let mut music = Music {
+                    title: title.to_string(),
+                    author: "David Bowie".to_string(),
+                    ..Default::default()
+                };
+                music.detect_version();
+                music
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(musics[1].base_title.as_deref(), Some("Heroes"));
+        assert_eq!(musics[2].version.as_deref(), Some("Live"));
+        assert_eq!(musics[0].version, None);
+
+        // title edited in the music file
+        let mut edited = Music {
+            title: "Starman".to_string(),
+            ..musics[1].clone()
+        };
+        edited.refresh_version();
+        assert_eq!((edited.base_title, edited.version), (None, None));
+
+        assert_eq!(
+            unique_music(musics.clone(), DedupOptions::default()).len(),
+            3
+        );
+        let options = DedupOptions {
+            merge_versions: true,
+            ..Default::default()
+        };
+        assert_eq!(unique_music(musics, options).len(), 1);
+    }
 }
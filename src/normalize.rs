@@ -0,0 +1,203 @@
+//! Normalization of titles and authors, to find duplicates
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// Featured artists between brackets, like "(feat. X)"
+///
+/// "(with X)" is not one, since it is part of titles like "(With Strings)"
+static FEATURING_BRACKETS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\s*[(\[]\s*(?:feat\.?|ft\.?|featuring)\s+([^)\]]*)[)\]]").expect("valid regex")
+});
+
+/// Featured artists at the end, like "A feat. B"
+static FEATURING_SUFFIX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\s+(?:feat\.?|ft\.?|featuring)\s+(.*)$").expect("valid regex")
+});
+
+/// Words of a version label, like "Remastered 2011" or "Radio Edit"
+static VERSION_WORDS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b(?:remaster(?:ed)?|live|edit|version|mono|stereo|acoustic|demo|extended|remix|mix|instrumental|unplugged|re-?recorded)\b",
+    )
+    .expect("valid regex")
+});
+
+/// Whole version label, made only of version words, their qualifiers and years
+static VERSION_LABEL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?:(?:remaster(?:ed)?|live|edit|version|mono|stereo|acoustic|demo|extended|remix|mix|instrumental|unplugged|re-?recorded|radio|single|album|original|club|deluxe|bonus|\d{4})\s*)+$",
+    )
+    .expect("valid regex")
+});
+
+/// Parts of a title that can be a version label, between brackets or after a dash
+/// (the last part after " - " can have hyphenated words, like "Re-Recorded")
+static VERSION_PARTS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\s*[(\[]([^)\]]*)[)\]]|\s+-\s+([^-]*(?:-[^\s-][^-]*)*)$").expect("valid regex")
+});
+
+/// Whether a whole part of a title is a version label, like "Radio Edit"
+///
+/// `"Live Forever"` is not one, even if it has a version word
+pub fn is_version_label(text: &str) -> bool {
+    VERSION_LABEL.is_match(text.trim())
+}
+
+/// Split the version label from a title
+///
+/// `"Heroes - 2017 Remaster"` gives `("Heroes", Some("2017 Remaster"))`
+pub fn split_version(title: &str) -> (String, Option<String>) {
+    let mut labels = vec![];
+    let base = VERSION_PARTS.replace_all(title, |caps: &regex::Captures<'_>| {
+        let part = caps
+            .get(1)
+            .or_else(|| caps.get(2))
+            .map_or("", |m| m.as_str());
+        if VERSION_WORDS.is_match(part) {
+            labels.push(part.trim().to_string());
+            String::new()
+        } else {
+            caps[0].to_string()
+        }
+    });
+    let base = base.trim();
+    if labels.is_empty() || base.is_empty() {
+        return (title.to_string(), None);
+    }
+    (base.to_string(), Some(labels.join(", ")))
+}
+
+/// Split the featured artists from a title or an author
+///
+/// `"One More Time (feat. Romanthony)"` gives `("One More Time", Some("Romanthony"))`
+pub fn split_featuring(text: &str) -> (String, Option<String>) {
+    let mut featured = vec![];
+    let text = FEATURING_BRACKETS.replace_all(text, |caps: &regex::Captures<'_>| {
+        featured.push(caps[1].trim().to_string());
+        ""
+    });
+    let text = match FEATURING_SUFFIX.captures(&text) {
+        Some(caps) => {
+            featured.push(caps[1].trim().to_string());
+            text[..caps.get(0).map_or(text.len(), |m| m.start())].to_string()
+        }
+        None => text.to_string(),
+    };
+    let featured = (!featured.is_empty()).then(|| featured.join(", "));
+    (text.trim().to_string(), featured)
+}
+
+/// Normalize a title or an author to compare it
+///
+/// The text is NFKC normalized, lowercased, without diacritics, apostrophes,
+/// featured artists and punctuation, "&" being replaced by "and".
+/// A text made only of punctuation (like "!!!") is only trimmed and lowercased,
+/// so it is not the same as every other such text
+pub fn normalize(text: &str) -> String {
+    let text = text.nfkc().collect::<String>();
+    let (without_featuring, _featured) = split_featuring(&text);
+    let folded = without_featuring
+        .to_lowercase()
+        .nfd()
+        .filter(|c| !is_combining_mark(*c) && !matches!(c, '\'' | '’'))
+        .collect::<String>()
+        .replace('&', " and ");
+    let normalized = folded
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if normalized.is_empty() {
+        text.trim().to_lowercase()
+    } else {
+        normalized
+    }
+}
+
+/// Similarity between two normalized texts, from 0 (different) to 1 (same)
+pub fn similarity(a: &str, b: &str) -> f64 {
+    strsim::normalized_levenshtein(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the normalization of titles and authors
+    /// # Panics
+    /// Panics if the normalization is wrong
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("  Beyoncé "), "beyonce");
+        assert_eq!(normalize("ＡＢＣ！"), "abc");
+        assert_eq!(normalize("Simon & Garfunkel"), "simon and garfunkel");
+        assert_eq!(normalize("Simon and Garfunkel"), "simon and garfunkel");
+        assert_eq!(normalize("Don't Stop Me Now"), "dont stop me now");
+        assert_eq!(
+            normalize("One More Time (feat. Romanthony) [Remix]"),
+            "one more time remix"
+        );
+        assert_eq!(
+            split_featuring("Daft Punk ft. Pharrell Williams"),
+            (
+                "Daft Punk".to_string(),
+                Some("Pharrell Williams".to_string())
+            )
+        );
+        assert_eq!(
+            split_featuring("Lose Yourself"),
+            ("Lose Yourself".to_string(), None)
+        );
+        assert_eq!(normalize("!!!"), "!!!");
+        assert_ne!(normalize("!!!"), normalize("???"));
+        assert_eq!(
+            split_featuring("Yesterday (With Strings)"),
+            ("Yesterday (With Strings)".to_string(), None)
+        );
+        assert!(similarity("one more time", "one more tyme") > 0.9);
+    }
+
+    /// Test the version labels of titles
+    /// # Panics
+    /// Panics if a version is not split as expected
+    #[test]
+    fn test_split_version() {
+        assert_eq!(
+            split_version("Heroes - 2017 Remaster"),
+            ("Heroes".to_string(), Some("2017 Remaster".to_string()))
+        );
+        assert_eq!(
+            split_version("Hey Jude (Remastered 2015) [Live]"),
+            (
+                "Hey Jude".to_string(),
+                Some("Remastered 2015, Live".to_string())
+            )
+        );
+        assert_eq!(
+            split_version("Get Lucky - Radio Edit"),
+            ("Get Lucky".to_string(), Some("Radio Edit".to_string()))
+        );
+        assert_eq!(
+            split_version("Love Story - Taylor's Version - Re-Recorded"),
+            (
+                "Love Story - Taylor's Version".to_string(),
+                Some("Re-Recorded".to_string())
+            )
+        );
+        assert_eq!(
+            split_version("Live Forever"),
+            ("Live Forever".to_string(), None)
+        );
+        assert_eq!(
+            split_version("Tonight (I'm Lovin' You)"),
+            ("Tonight (I'm Lovin' You)".to_string(), None)
+        );
+        assert!(is_version_label("Radio Edit"));
+        assert!(is_version_label("2011 Remaster"));
+        assert!(!is_version_label("Live Forever"));
+        assert!(!is_version_label("Radio Gaga"));
+    }
+}
@@ -9,9 +9,10 @@ use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use tokio::net::TcpListener;
 use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
 use url::Url;
 
-use crate::errors::MusicExporterError;
+use crate::errors::{ErrorKind, MusicExporterError};
 
 /// Received authorization code
 #[derive(Debug, Clone)]
@@ -82,22 +83,32 @@ impl Service<Request<hyper::body::Incoming>> for OAuthService {
 
 /// Listen for the authorization code
 /// # Errors
-/// Returns `Err` if the server fails to bind or if the authorization code is not received
-pub async fn listen_for_code(port: u32) -> Result<ReceivedCode, ()> {
+/// Returns `Err` if the server fails to bind, if the authorization code is not received
+/// or if the user cancelled with CTRL+C
+pub async fn listen_for_code(
+    port: u32,
+    cancel: CancellationToken,
+) -> Result<ReceivedCode, MusicExporterError> {
     let bind = format!("127.0.0.1:{}", port);
     log::info!("Listening on: http://{}", bind);
     let addr: SocketAddr = match str::parse(&bind) {
         Ok(addr) => addr,
         Err(_) => {
             log::error!("Invalid address: {}", bind);
-            return Err(());
+            return Err(
+                MusicExporterError::new(format!("Invalid address: {}", bind))
+                    .with_kind(ErrorKind::Config),
+            );
         }
     };
     let listener = match TcpListener::bind(addr).await {
         Ok(listener) => listener,
         Err(err) => {
             log::error!("Failed to bind: {}", err);
-            return Err(());
+            return Err(MusicExporterError::new_with_source(
+                format!("Failed to bind {}", bind),
+                err.into(),
+            ));
         }
     };
     let (tx, mut rx) = oneshot::channel();
@@ -135,6 +146,9 @@ pub async fn listen_for_code(port: u32) -> Result<ReceivedCode, ()> {
             _ = tokio::signal::ctrl_c()  => {
                 println!("CTRL+C was used");
                 break;
+            },
+            _ = cancel.cancelled() => {
+                break;
             }
         }
     }
@@ -148,12 +162,15 @@ pub async fn listen_for_code(port: u32) -> Result<ReceivedCode, ()> {
             }
             Err(_) => {
                 log::info!("Error receiving authorization code");
-                Err(())
+                Err(
+                    MusicExporterError::new("Error receiving authorization code")
+                        .with_kind(ErrorKind::Auth { platform: None }),
+                )
             }
         },
         None => {
             log::info!("CTRL+C was used");
-            Err(())
+            Err(MusicExporterError::new("Authorization cancelled").with_kind(ErrorKind::Cancelled))
         }
     }
 }
@@ -0,0 +1,164 @@
+//! Corrections of the musics given by the user
+
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::{
+    errors::{ErrorKind, MusicExporterError},
+    normalize::{normalize, split_featuring},
+    Music,
+};
+
+/// Correction of a music, the missing fields are kept
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct TrackOverride {
+    /// Corrected title
+    #[serde(default)]
+    pub title: Option<String>,
+
+    /// Corrected author
+    #[serde(default)]
+    pub author: Option<String>,
+
+    /// Corrected album
+    #[serde(default)]
+    pub album: Option<String>,
+}
+
+/// Content of an overrides file
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+pub struct Overrides {
+    /// Right name of each artist alias
+    #[serde(default)]
+    pub artists: HashMap<String, String>,
+
+    /// Correction of each music, by URL or platform id
+    #[serde(default)]
+    pub tracks: HashMap<String, TrackOverride>,
+}
+
+impl Overrides {
+    /// Load a JSON overrides file
+    /// # Errors
+    /// Config error if the file cannot be read or is invalid
+    pub fn from_file(path: &Path) -> Result<Self, MusicExporterError> {
+        fs::read_to_string(path)
+            .map_err(MusicExporterError::from)
+            .and_then(|content| Ok(serde_json::from_str(&content)?))
+            .map_err(|e| {
+                MusicExporterError::new_with_source(
+                    format!("Cannot load the overrides file {}", path.display()),
+                    e,
+                )
+                .with_kind(ErrorKind::Config)
+            })
+    }
+
+    /// Apply the corrections to the musics, returning the number of musics changed
+    pub fn apply<'a>(&self, musics: impl IntoIterator<Item = &'a mut Music>) -> usize {
+        let aliases = self
+            .artists
+            .iter()
+            .map(|(alias, name)| (normalize(alias), name))
+            .collect::<HashMap<_, _>>();
+        let mut changed = 0;
+        for music in musics {
+            let before = (
+                music.title.clone(),
+                music.author.clone(),
+                music.album.clone(),
+            );
+            // the featured artists are kept, "AC DC feat. X" giving "AC/DC feat. X"
+            let (main, featured) = split_featuring(&music.author);
+            if let Some(name) = aliases.get(&normalize(&main)) {
+                let rest = match (music.author.strip_prefix(main.as_str()), featured) {
+                    (Some(rest), _) => rest.to_string(),
+                    (None, Some(featured)) => format!(" feat. {}", featured),
+                    (None, None) => String::new(),
+                };
+                music.author = format!("{}{}", name, rest);
+            }
+            if let Some(correction) = self.track_override(music) {
+                if let Some(title) = &correction.title {
+                    music.title = title.clone();
+                }
+                if let Some(author) = &correction.author {
+                    music.author = author.clone();
+                }
+                if let Some(album) = &correction.album {
+                    music.album = Some(album.clone());
+                }
+            }
+            if music.title != before.0 {
+                music.refresh_version();
+            }
+            if (&music.title, &music.author, &music.album) != (&before.0, &before.1, &before.2) {
+                log::debug!("Corrected: {} by {}", music.title, music.author);
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// Correction of a music, by URL or else by platform id
+    fn track_override(&self, music: &Music) -> Option<&TrackOverride> {
+        music
+            .url
+            .as_ref()
+            .and_then(|url| self.tracks.get(url))
+            .or_else(|| self.tracks.get(&music.platform_id()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the artist aliases and the corrections of the musics
+    /// # Panics
+    /// If the assertion fails
+    #[test]
+    fn test_overrides() {
+        let overrides: Overrides = serde_json::from_str(
+            r#"{
+                "artists": { "AC DC": "AC/DC" },
+                "tracks": {
+                    "https://www.deezer.com/track/1": { "album": "Back in Black" },
+                    "dQw4w9WgXcQ": { "title": "Never Gonna Give You Up", "author": "Rick Astley" }
+                }
+            }"#,
+        )
+        .unwrap();
+        let mut musics = vec![
+            Music {
+                title: "Hells Bells".to_string(),
+                author: "ac-dc".to_string(),
+                url: Some("https://www.deezer.com/track/1".to_string()),
+                ..Default::default()
+            },
+            Music {
+                title: "Rick Astley - Never Gonna Give You Up (Live)".to_string(),
+                author: "RickAstleyVEVO".to_string(),
+                url: Some("https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string()),
+                ..Default::default()
+            },
+            Music {
+                title: "Other".to_string(),
+                author: "Other".to_string(),
+                ..Default::default()
+            },
+            Music {
+                title: "Highway to Hell".to_string(),
+                author: "ac-dc feat. Someone".to_string(),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(overrides.apply(&mut musics), 3);
+        assert_eq!(musics[0].author, "AC/DC");
+        assert_eq!(musics[0].album.as_deref(), Some("Back in Black"));
+        assert_eq!(musics[1].title, "Never Gonna Give You Up");
+        assert_eq!(musics[1].author, "Rick Astley");
+        assert_eq!(musics[1].version, None);
+        assert_eq!(musics[3].author, "AC/DC feat. Someone");
+        assert_eq!(overrides.apply(&mut musics), 0);
+    }
+}
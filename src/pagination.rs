@@ -0,0 +1,527 @@
+//! Pagination of the platform lists
+
+use futures_util::{stream, StreamExt, TryStreamExt};
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    future::Future,
+    io::Write,
+    path::PathBuf,
+    sync::Arc,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    errors::{ErrorKind, MusicExporterError},
+    storage::with_suffix,
+    Music, MusicExporter, PlatformType,
+};
+
+/// Position of a page in a list
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Cursor {
+    /// Numeric offset (Spotify, Deezer)
+    Offset(u64),
+
+    /// Opaque page token (Youtube)
+    Token(String),
+}
+
+impl Cursor {
+    /// Offset of the cursor, if numeric
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            Cursor::Offset(offset) => Some(*offset),
+            Cursor::Token(_) => None,
+        }
+    }
+
+    /// Token of the cursor, if opaque
+    pub fn token(&self) -> Option<&str> {
+        match self {
+            Cursor::Offset(_) => None,
+            Cursor::Token(token) => Some(token),
+        }
+    }
+}
+
+/// Page of a list
+#[derive(Debug, Clone)]
+pub struct Page {
+    /// Musics of the page
+    pub items: Vec<Music>,
+
+    /// Cursor of the next page
+    pub next: Option<Cursor>,
+
+    /// Total number of items of the list, if known
+    pub total: Option<u64>,
+}
+
+/// Page saved in the checkpoint of an interrupted export
+///
+/// The checkpoint file has one JSON line per page, in the order of the list
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Checkpoint {
+    /// Platform exported
+    platform: PlatformType,
+
+    /// Cursor of the page after this one
+    next: Option<Cursor>,
+
+    /// Total number of items of the list, if known
+    #[serde(default)]
+    total: Option<u64>,
+
+    /// Musics of the page
+    items: Vec<Music>,
+}
+
+/// Drive the pagination of a platform list
+#[derive(Debug, Clone)]
+pub struct Paginator {
+    /// Platform paginated
+    platform: PlatformType,
+
+    /// Maximum number of pages fetched at the same time
+    concurrency: usize,
+
+    /// Platform ids of the already known musics (incremental export)
+    known: Option<Arc<HashSet<String>>>,
+
+    /// Checkpoint file, a page is appended after each fetch
+    checkpoint: Option<PathBuf>,
+
+    /// Continue from the checkpoint file, if any
+    resume: bool,
+
+    /// Stop fetching when cancelled
+    cancel: CancellationToken,
+
+    /// Return the musics fetched so far when cancelled, instead of an error
+    keep_partial: bool,
+}
+
+impl Paginator {
+    /// Create the paginator of a platform
+    pub(crate) fn new(platform: PlatformType, args: &MusicExporter) -> Self {
+        Self {
+            platform,
+            concurrency: args.page_concurrency.max(1),
+            known: None,
+            checkpoint: Some(Self::checkpoint_path(platform, args)),
+            resume: args.resume,
+            cancel: args.cancellation_token(),
+            keep_partial: args.save_on_cancel,
+        }
+    }
+
+    /// Path of the checkpoint file of a platform, next to the music file
+    fn checkpoint_path(platform: PlatformType, args: &MusicExporter) -> PathBuf {
+        let suffix = format!(".{}.checkpoint", platform.to_string().to_lowercase());
+        with_suffix(&args.music_file, &suffix)
+    }
+
+    /// Load the pages of the checkpoint of the platform when resuming
+    ///
+    /// A torn last line (export killed while writing) is dropped from the file
+    /// # Errors
+    /// Error if the checkpoint exists but cannot be read
+    fn load_checkpoint(&self) -> Result<Option<Vec<Checkpoint>>, MusicExporterError> {
+        let path = match &self.checkpoint {
+            Some(path) if self.resume && path.exists() => path,
+            _ => return Ok(None),
+        };
+        let content = fs::read_to_string(path)?;
+        let mut pages = vec![];
+        let mut valid = 0;
+        let mut lines = content.split_inclusive('\n').peekable();
+        while let Some(line) = lines.next() {
+            let page = serde_json::from_str::<Checkpoint>(line)
+                .ok()
+                .filter(|_| line.ends_with('\n'));
+            match page {
+                Some(page) if page.platform != self.platform => {
+                    return Err(MusicExporterError::new(format!(
+                        "Checkpoint {} is not for {}",
+                        path.display(),
+                        self.platform
+                    )));
+                }
+                Some(page) => {
+                    valid += line.len();
+                    pages.push(page);
+                }
+                None if lines.peek().is_none() => {
+                    log::warn!(
+                        "{}: dropping the incomplete last page of {}",
+                        self.platform,
+                        path.display()
+                    );
+                    OpenOptions::new()
+                        .write(true)
+                        .open(path)?
+                        .set_len(valid as u64)?;
+                }
+                None => {
+                    return Err(MusicExporterError::new(format!(
+                        "Invalid checkpoint {}",
+                        path.display()
+                    ))
+                    .with_kind(ErrorKind::Parse));
+                }
+            }
+        }
+        Ok(Some(pages).filter(|pages| !pages.is_empty()))
+    }
+
+    /// Append a page to the checkpoint, or start a new checkpoint with it
+    /// # Errors
+    /// Error if the checkpoint cannot be written
+    fn save_checkpoint(&self, page: &Page, append: bool) -> Result<(), MusicExporterError> {
+        let Some(path) = &self.checkpoint else {
+            return Ok(());
+        };
+        let checkpoint = Checkpoint {
+            platform: self.platform,
+            next: page.next.clone(),
+            total: page.total,
+            items: page.items.clone(),
+        };
+        let mut line = serde_json::to_vec(&checkpoint)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)?;
+        file.write_all(&line)?;
+        Ok(())
+    }
+
+    /// Remove the checkpoint once the list is complete
+    /// # Errors
+    /// Error if the checkpoint cannot be removed
+    fn clear_checkpoint(&self) -> Result<(), MusicExporterError> {
+        match &self.checkpoint {
+            Some(path) if path.exists() => Ok(fs::remove_file(path)?),
+            _ => Ok(()),
+        }
+    }
+
+    /// Neither save nor resume a checkpoint, when nothing is written
+    pub(crate) fn without_checkpoint(mut self) -> Self {
+        self.checkpoint = None;
+        self
+    }
+
+    /// Stop the pagination at the first page made only of known musics
+    ///
+    /// Liked lists are newest-first, so the rest of the list is already known.
+    /// Pages are then fetched one after the other
+    pub(crate) fn with_known(mut self, known: Arc<HashSet<String>>) -> Self {
+        self.known = Some(known);
+        self
+    }
+
+    /// Check if all the musics of the page are already known
+    fn is_known(&self, page: &Page) -> bool {
+        match &self.known {
+            Some(known) => {
+                !page.items.is_empty()
+                    && page.items.iter().all(|music| {
+                        music
+                            .platform_id()
+                            .is_some_and(|platform_id| known.contains(&platform_id))
+                    })
+            }
+            None => false,
+        }
+    }
+
+    /// Fetch all the pages of a list, starting with `fetch(None)`
+    ///
+    /// When the first page gives the total and a numeric offset,
+    /// the remaining pages are fetched concurrently and reassembled in order.
+    /// Each page is appended to a checkpoint, so an interrupted export can be resumed
+    /// # Errors
+    /// Error if a page fails
+    pub async fn collect<F, Fut>(&self, fetch: F) -> Result<Vec<Music>, MusicExporterError>
+    where
+        F: Fn(Option<Cursor>) -> Fut,
+        Fut: Future<Output = Result<Page, MusicExporterError>>,
+    {
+        let items = self.collect_pages(fetch).await.inspect_err(|_| {
+            if let Some(path) = &self.checkpoint {
+                if path.exists() {
+                    log::warn!(
+                        "{}: progress saved in {}, use --resume to continue",
+                        self.platform,
+                        path.display()
+                    );
+                }
+            }
+        })?;
+        if self.cancel.is_cancelled() {
+            log::warn!(
+                "{}: cancelled with {} items, use --resume to continue",
+                self.platform,
+                items.len()
+            );
+        } else {
+            self.clear_checkpoint()?;
+        }
+        Ok(items)
+    }
+
+    /// Wait for `future`, unless the export is cancelled (`None`)
+    /// # Errors
+    /// Error if `future` fails or if cancelled without keeping the partial musics
+    async fn cancellable<T, Fut>(&self, future: Fut) -> Result<Option<T>, MusicExporterError>
+    where
+        Fut: Future<Output = Result<T, MusicExporterError>>,
+    {
+        tokio::select! {
+            value = future => value.map(Some),
+            _ = self.cancel.cancelled() => {
+                if self.keep_partial {
+                    Ok(None)
+                } else {
+                    Err(MusicExporterError::new(format!("{} export cancelled", self.platform))
+                        .with_kind(ErrorKind::Cancelled))
+                }
+            }
+        }
+    }
+
+    /// Fetch the pages of a list, appending each page to the checkpoint
+    ///
+    /// A resumed export keeps fetching the remaining pages concurrently
+    /// # Errors
+    /// Error if a page fails
+    async fn collect_pages<F, Fut>(&self, fetch: F) -> Result<Vec<Music>, MusicExporterError>
+    where
+        F: Fn(Option<Cursor>) -> Fut,
+        Fut: Future<Output = Result<Page, MusicExporterError>>,
+    {
+        let (mut items, mut next, total, page_size) = match self.load_checkpoint()? {
+            Some(pages) => {
+                // the first page starts at 0, so its size is the next offset
+                let page_size = pages[0].next.as_ref().and_then(Cursor::offset);
+                let total = pages.iter().rev().find_map(|page| page.total);
+                let next = pages.last().and_then(|page| page.next.clone());
+                let items = pages
+                    .into_iter()
+                    .flat_map(|page| page.items)
+                    .collect::<Vec<_>>();
+                log::info!(
+                    "{}: resuming with {} items at {:?}",
+                    self.platform,
+                    items.len(),
+                    next
+                );
+                (items, next, total, page_size)
+            }
+            None => {
+                let Some(first) = self.cancellable(fetch(None)).await? else {
+                    return Ok(vec![]);
+                };
+                if self.is_known(&first) {
+                    log::info!("{}: no new musics", self.platform);
+                    return Ok(first.items);
+                }
+                self.save_checkpoint(&first, false)?;
+                let page_size = first.next.as_ref().and_then(Cursor::offset);
+                (first.items, first.next, first.total, page_size)
+            }
+        };
+        if let (Some(Cursor::Offset(start)), Some(total), Some(page_size)) =
+            (&next, total, page_size)
+        {
+            if self.concurrency > 1 && page_size > 0 && self.known.is_none() {
+                let offsets = (*start..total).step_by(page_size as usize);
+                log::info!(
+                    "{}: fetching {} pages ({} at a time)",
+                    self.platform,
+                    total.saturating_sub(*start).div_ceil(page_size),
+                    self.concurrency
+                );
+                let mut pages = stream::iter(offsets)
+                    .map(|offset| fetch(Some(Cursor::Offset(offset))))
+                    .buffered(self.concurrency);
+                while let Some(Some(page)) = self.cancellable(pages.try_next()).await? {
+                    self.save_checkpoint(&page, true)?;
+                    items.extend(page.items);
+                }
+                return Ok(items);
+            }
+        }
+        while next.is_some() {
+            let Some(page) = self.cancellable(fetch(next)).await? else {
+                break;
+            };
+            self.save_checkpoint(&page, true)?;
+            let is_known = self.is_known(&page);
+            items.extend(page.items);
+            next = page.next;
+            if is_known {
+                log::info!("{}: reached already known musics", self.platform);
+                break;
+            }
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Paginator without checkpoint
+    fn test_paginator(concurrency: usize) -> Paginator {
+        Paginator {
+            platform: PlatformType::Deezer,
+            concurrency,
+            known: None,
+            checkpoint: None,
+            resume: false,
+            cancel: CancellationToken::new(),
+            keep_partial: false,
+        }
+    }
+
+    /// Fake page of 10 items out of 95
+    /// # Errors
+    /// Never fails
+    async fn fake_page(offset: u64) -> Result<Page, MusicExporterError> {
+        // later pages answer first
+        tokio::time::sleep(std::time::Duration::from_millis(100 - offset)).await;
+        let items = (offset..(offset + 10).min(95))
+            .map(|idx| Music {
+                author: "author".to_string(),
+                title: idx.to_string(),
+                url: Some(format!("https://www.deezer.com/track/{}", idx)),
+                thumbnail: None,
+                date: None,
+                album: None,
+                ..Default::default()
+            })
+            .collect();
+        let next = Some(offset + 10).filter(|next| *next < 95);
+        Ok(Page {
+            items,
+            next: next.map(Cursor::Offset),
+            total: Some(95),
+        })
+    }
+
+    /// Test that concurrent pages are reassembled in order
+    /// # Panics
+    /// If the assertion fails
+    #[tokio::test]
+    async fn test_collect_in_order() {
+        for concurrency in [1, 4] {
+            let paginator = test_paginator(concurrency);
+            let items = paginator
+                .collect(|cursor| fake_page(cursor.and_then(|c| c.offset()).unwrap_or(0)))
+                .await
+                .unwrap();
+            let titles = items.iter().map(|m| m.title.clone()).collect::<Vec<_>>();
+            let expected = (0..95).map(|idx| idx.to_string()).collect::<Vec<_>>();
+            assert_eq!(titles, expected);
+        }
+    }
+
+    /// Test that the incremental pagination stops at known musics
+    /// # Panics
+    /// If the assertion fails
+    #[tokio::test]
+    async fn test_collect_incremental() {
+        let known = (20..95).map(|idx| idx.to_string()).collect::<HashSet<_>>();
+        let paginator = test_paginator(4).with_known(Arc::new(known));
+        let items = paginator
+            .collect(|cursor| fake_page(cursor.and_then(|c| c.offset()).unwrap_or(0)))
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 30);
+    }
+
+    /// Test that an export resumes from its checkpoint
+    /// # Panics
+    /// If the assertion fails
+    #[tokio::test]
+    async fn test_collect_resume() {
+        let path = std::env::temp_dir().join(format!(
+            "music-exporter-{}.deezer.checkpoint",
+            std::process::id()
+        ));
+        let mut paginator = test_paginator(4);
+        paginator.checkpoint = Some(path.clone());
+        paginator.resume = true;
+        paginator
+            .save_checkpoint(&fake_page(0).await.unwrap(), false)
+            .unwrap();
+        paginator
+            .save_checkpoint(&fake_page(10).await.unwrap(), true)
+            .unwrap();
+        // export killed while writing the third page
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"platform":"Deezer","next":"#).unwrap();
+        let pages = paginator.load_checkpoint().unwrap().unwrap();
+        assert_eq!(pages.len(), 2);
+        assert!(fs::read_to_string(&path).unwrap().ends_with('\n'));
+
+        let items = paginator
+            .collect(|cursor| {
+                let offset = cursor.and_then(|c| c.offset());
+                assert!(offset >= Some(20), "the saved pages are not fetched again");
+                fake_page(offset.unwrap_or(0))
+            })
+            .await
+            .unwrap();
+        let titles = items.iter().map(|m| m.title.clone()).collect::<Vec<_>>();
+        let expected = (0..95).map(|idx| idx.to_string()).collect::<Vec<_>>();
+        assert_eq!(titles, expected);
+        assert!(!path.exists());
+        paginator.resume = false;
+        assert!(paginator.load_checkpoint().unwrap().is_none());
+
+        fs::write(&path, "{}\n{}\n").unwrap();
+        paginator.resume = true;
+        let err = paginator.load_checkpoint().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Parse);
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// Test that a cancelled export keeps the musics fetched so far
+    /// # Panics
+    /// If the assertion fails
+    #[tokio::test]
+    async fn test_collect_cancelled() {
+        let mut paginator = test_paginator(1);
+        let cancel = paginator.cancel.clone();
+        let fetch = |cursor: Option<Cursor>| {
+            let offset = cursor.and_then(|c| c.offset()).unwrap_or(0);
+            if offset == 30 {
+                cancel.cancel();
+            }
+            fake_page(offset)
+        };
+        let err = paginator.collect(fetch).await.unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Cancelled);
+
+        paginator.cancel = CancellationToken::new();
+        paginator.keep_partial = true;
+        let cancel = paginator.cancel.clone();
+        let fetch = |cursor: Option<Cursor>| {
+            let offset = cursor.and_then(|c| c.offset()).unwrap_or(0);
+            if offset == 30 {
+                cancel.cancel();
+            }
+            fake_page(offset)
+        };
+        let items = paginator.collect(fetch).await.unwrap();
+        assert_eq!(items.len(), 30);
+    }
+}
@@ -0,0 +1,297 @@
+//! Rules cleaning the titles and authors of the platforms
+
+use std::{fs, path::Path};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    errors::{ErrorKind, MusicExporterError},
+    Music, PlatformType,
+};
+
+/// Field of a music a rule applies to
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum RuleField {
+    /// Title of the music
+    #[default]
+    Title,
+
+    /// Author of the music
+    Author,
+
+    /// Title and author of the music
+    Both,
+}
+
+/// Rule cleaning the titles or authors, as written in the rules file
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+pub struct CleaningRule {
+    /// Regex (case insensitive) of the parts to replace
+    #[serde(default)]
+    pub regex: Option<String>,
+
+    /// Keyword removing the parts between brackets that contain it
+    #[serde(default)]
+    pub keyword: Option<String>,
+
+    /// Replacement of the matches, they are removed by default
+    #[serde(default)]
+    pub replace: String,
+
+    /// Field the rule applies to
+    #[serde(default)]
+    pub field: RuleField,
+
+    /// Platforms the rule applies to, all of them when empty
+    #[serde(default)]
+    pub platforms: Vec<PlatformType>,
+}
+
+/// Content of a rules file
+#[derive(Debug, serde::Deserialize)]
+struct RulesFile {
+    /// Whether the default rules are applied before the rules of the file
+    #[serde(default = "default_true")]
+    use_defaults: bool,
+
+    /// Rules of the file
+    #[serde(default)]
+    rules: Vec<CleaningRule>,
+}
+
+/// Default value of [`RulesFile::use_defaults`]
+fn default_true() -> bool {
+    true
+}
+
+/// Compiled cleaning rule
+#[derive(Debug, Clone)]
+struct Rule {
+    /// Regex of the parts to replace
+    regex: Regex,
+
+    /// Replacement of the matches
+    replace: String,
+
+    /// Field the rule applies to
+    field: RuleField,
+
+    /// Platforms the rule applies to, all of them when empty
+    platforms: Vec<PlatformType>,
+}
+
+impl Rule {
+    /// Compile a rule
+    /// # Errors
+    /// Config error if the regex is invalid, or if the rule has no regex nor keyword
+    fn compile(rule: &CleaningRule) -> Result<Self, MusicExporterError> {
+        let pattern = match (&rule.regex, &rule.keyword) {
+            (Some(regex), None) => regex.clone(),
+            (None, Some(keyword)) => {
+                format!(r"\s*[(\[][^)\]]*{}[^)\]]*[)\]]", regex::escape(keyword))
+            }
+            _ => {
+                return Err(MusicExporterError::new(
+                    "A cleaning rule needs either a regex or a keyword",
+                )
+                .with_kind(ErrorKind::Config))
+            }
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| {
+                MusicExporterError::new(format!("Invalid cleaning rule {}: {}", pattern, e))
+                    .with_kind(ErrorKind::Config)
+            })?;
+        Ok(Self {
+            regex,
+            replace: rule.replace.clone(),
+            field: rule.field,
+            platforms: rule.platforms.clone(),
+        })
+    }
+
+    /// Whether the rule applies to the field of a music of the platform
+    fn applies(&self, platform: PlatformType, field: RuleField) -> bool {
+        (self.field == field || self.field == RuleField::Both)
+            && (self.platforms.is_empty() || self.platforms.contains(&platform))
+    }
+}
+
+/// Default rules, removing the "Official Video", "Lyrics", "HD"... of the Youtube titles
+///
+/// The other platforms keep their titles, where "(Lyric Version)" or "(Audio Mix)"
+/// are version labels
+pub fn default_rules() -> Vec<CleaningRule> {
+    let keyword = |keyword: &str| CleaningRule {
+        keyword: Some(keyword.to_string()),
+        platforms: vec![PlatformType::Youtube],
+        ..Default::default()
+    };
+    let mut rules = [
+        "offic", "lyric", "paroles", "video", "audio", "clip", "visuali",
+    ]
+    .into_iter()
+    .map(keyword)
+    .collect::<Vec<_>>();
+    rules.push(CleaningRule {
+        regex: Some(r"\s*[(\[]\s*(?:hd|hq|4k|mv|m/v)\s*[)\]]".to_string()),
+        platforms: vec![PlatformType::Youtube],
+        ..Default::default()
+    });
+    rules.push(CleaningRule {
+        regex: Some(r"\s+[-|]\s+(?:official\b.*|clip officiel|lyrics?)$".to_string()),
+        platforms: vec![PlatformType::Youtube],
+        ..Default::default()
+    });
+    rules.push(CleaningRule {
+        regex: Some(r"\s+-\s+topic$".to_string()),
+        field: RuleField::Author,
+        platforms: vec![PlatformType::Youtube],
+        ..Default::default()
+    });
+    rules
+}
+
+/// Rules cleaning the titles and authors of the musics of the platforms
+#[derive(Debug, Clone)]
+pub struct CleaningRules {
+    /// Rules, in the order they are applied
+    rules: Vec<Rule>,
+}
+
+impl Default for CleaningRules {
+    fn default() -> Self {
+        Self::new(&default_rules()).expect("valid default rules")
+    }
+}
+
+impl CleaningRules {
+    /// Compile the rules
+    /// # Errors
+    /// Config error if a rule is invalid
+    pub fn new(rules: &[CleaningRule]) -> Result<Self, MusicExporterError> {
+        Ok(Self {
+            rules: rules.iter().map(Rule::compile).collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Load the rules of a JSON rules file, after the default rules unless `use_defaults` is false
+    /// # Errors
+    /// Config error if the file cannot be read or a rule is invalid
+    pub fn from_file(path: &Path) -> Result<Self, MusicExporterError> {
+        let file = fs::read_to_string(path)
+            .map_err(MusicExporterError::from)
+            .and_then(|content| Ok(serde_json::from_str::<RulesFile>(&content)?))
+            .map_err(|e| {
+                MusicExporterError::new_with_source(
+                    format!("Cannot load the rules file {}", path.display()),
+                    e,
+                )
+                .with_kind(ErrorKind::Config)
+            })?;
+        let mut rules = if file.use_defaults {
+            default_rules()
+        } else {
+            vec![]
+        };
+        rules.extend(file.rules);
+        Self::new(&rules)
+    }
+
+    /// Clean a text of a music of the platform
+    ///
+    /// The text is kept as it is if the rules would leave nothing of it
+    pub fn apply(&self, platform: PlatformType, field: RuleField, text: &str) -> String {
+        let mut cleaned = text.to_string();
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| rule.applies(platform, field))
+        {
+            cleaned = rule
+                .regex
+                .replace_all(&cleaned, rule.replace.as_str())
+                .into_owned();
+        }
+        match cleaned.trim() {
+            "" => text.to_string(),
+            cleaned => cleaned.to_string(),
+        }
+    }
+
+    /// Clean the title and author of a music of the platform
+    pub fn clean(&self, platform: PlatformType, music: &mut Music) {
+        music.title = self.apply(platform, RuleField::Title, &music.title);
+        music.author = self.apply(platform, RuleField::Author, &music.author);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the default rules
+    /// # Panics
+    /// If the assertion fails
+    #[test]
+    fn test_default_rules() {
+        let rules = CleaningRules::default();
+        let clean_title = |title| rules.apply(PlatformType::Youtube, RuleField::Title, title);
+        let title = "title (feat. artist)";
+        assert_eq!(clean_title(title), "title (feat. artist)");
+        let title = "title (audio officiel)";
+        assert_eq!(clean_title(title), "title");
+        let title = "title [new]";
+        assert_eq!(clean_title(title), "title [new]");
+        let title = "title [official video]";
+        assert_eq!(clean_title(title), "title");
+        assert_eq!(clean_title("title (Lyrics) [HD]"), "title");
+        assert_eq!(clean_title("title (MV)"), "title");
+        assert_eq!(clean_title("title - Clip Officiel"), "title");
+        assert_eq!(clean_title("(Official Video)"), "(Official Video)");
+        assert_eq!(
+            rules.apply(PlatformType::Youtube, RuleField::Author, "Artist - Topic"),
+            "Artist"
+        );
+        assert_eq!(
+            rules.apply(PlatformType::Deezer, RuleField::Author, "Artist - Topic"),
+            "Artist - Topic"
+        );
+        for platform in [PlatformType::Spotify, PlatformType::Deezer] {
+            assert_eq!(
+                rules.apply(platform, RuleField::Title, "title (Lyric Version)"),
+                "title (Lyric Version)"
+            );
+        }
+    }
+
+    /// Test the user rules
+    /// # Panics
+    /// If the assertion fails
+    #[test]
+    fn test_user_rules() {
+        let rules = CleaningRules::new(&[CleaningRule {
+            regex: Some(r"\s*\[explicit\]".to_string()),
+            field: RuleField::Both,
+            platforms: vec![PlatformType::Spotify],
+            ..Default::default()
+        }])
+        .unwrap();
+        let mut music = Music {
+            title: "Song [Explicit]".to_string(),
+            author: "Artist [explicit]".to_string(),
+            ..Default::default()
+        };
+        let mut other = music.clone();
+        rules.clean(PlatformType::Spotify, &mut music);
+        assert_eq!(music.title, "Song");
+        assert_eq!(music.author, "Artist");
+        rules.clean(PlatformType::Deezer, &mut other);
+        assert_eq!(other.title, "Song [Explicit]");
+        assert!(CleaningRules::new(&[CleaningRule::default()]).is_err());
+    }
+}
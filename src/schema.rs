@@ -0,0 +1,275 @@
+//! Versioned schema of the music file
+
+use crate::{Music, PlatformType};
+
+/// Current version of the music file schema
+///
+/// - 1: flat array of musics
+/// - 2: envelope with metadata
+pub const SCHEMA_VERSION: u64 = 2;
+
+/// Content of the music file
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MusicFile {
+    /// Version of the schema
+    pub schema_version: u64,
+
+    /// Name and version of the program that wrote the file
+    #[serde(default)]
+    pub generator: Option<String>,
+
+    /// Date of the export (RFC 3339)
+    #[serde(default)]
+    pub exported_at: Option<String>,
+
+    /// Platforms exported into the file
+    #[serde(default)]
+    pub sources: Vec<PlatformType>,
+
+    /// Musics
+    pub musics: Vec<Music>,
+}
+
+impl MusicFile {
+    /// New music file of the current version, exported now
+    pub fn new(musics: Vec<Music>, sources: Vec<PlatformType>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            generator: Some(format!(
+                "{} {}",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            )),
+            exported_at: Some(jiff::Timestamp::now().to_string()),
+            sources,
+            musics,
+        }
+    }
+
+    /// Store the musics exported from `platforms`, keeping the other metadata
+    ///
+    /// The export date and the generator only change when the musics changed
+    pub fn update(&mut self, musics: Vec<Music>, platforms: &[PlatformType]) {
+        for platform in platforms {
+            if !self.sources.contains(platform) {
+                self.sources.push(*platform);
+            }
+        }
+        // `Music` equality only compares the key of the musics
+        if serde_json::to_value(&self.musics).ok() != serde_json::to_value(&musics).ok() {
+            let exported = Self::new(vec![], vec![]);
+            self.generator = exported.generator;
+            self.exported_at = exported.exported_at;
+            self.musics = musics;
+        }
+    }
+
+    /// Parse a music file of any version, migrating it to the current version
+    /// # Errors
+    /// Error if the content is not a valid music file
+    pub fn parse(content: &str) -> Result<Self, serde_json::Error> {
+        let value = serde_json::from_str::<serde_json::Value>(content)?;
+        match version_of(&value) {
+            // parse the text again to get the position of the errors
+            Some(SCHEMA_VERSION) => serde_json::from_str(content),
+            _ => serde_json::from_value(migrate(value)?),
+        }
+    }
+
+    /// JSON Schema of the current version
+    pub fn json_schema() -> serde_json::Value {
+        let nullable_string = serde_json::json!({ "type": ["string", "null"] });
+        serde_json::json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "music-exporter music file",
+            "type": "object",
+            "required": ["schema_version", "musics"],
+            "properties": {
+                "schema_version": { "const": SCHEMA_VERSION },
+                "generator": nullable_string,
+                "exported_at": { "type": ["string", "null"], "format": "date-time" },
+                "sources": {
+                    "type": "array",
+                    "items": { "enum": ["deezer", "spotify", "youtube"] }
+                },
+                "musics": {
+                    "type": "array",
+                    "items": { "$ref": "#/$defs/music" }
+                }
+            },
+            "$defs": {
+                "music": {
+                    "type": "object",
+                    "required": ["author", "title"],
+                    "properties": {
+                        "author": { "type": "string" },
+                        "title": { "type": "string" },
+                        "url": nullable_string,
+                        "thumbnail": nullable_string,
+                        "date": nullable_string,
+                        "album": nullable_string,
+                        "original_title": { "type": "string" },
+                        "original_author": { "type": "string" },
+                        "base_title": { "type": "string" },
+                        "version": { "type": "string" },
+                        "tags": { "type": "array", "items": { "type": "string" } },
+                        "rating": { "type": "integer", "minimum": 0, "maximum": 255 },
+                        "notes": { "type": "string" },
+                        "first_seen": { "type": "string", "format": "date-time" },
+                        "last_seen": { "type": "string", "format": "date-time" },
+                        "sources": {
+                            "type": "object",
+                            "propertyNames": { "enum": ["deezer", "spotify", "youtube"] },
+                            "additionalProperties": {
+                                "type": "object",
+                                "properties": {
+                                    "url": { "type": "string" },
+                                    "first_seen": { "type": "string", "format": "date-time" },
+                                    "last_seen": { "type": "string", "format": "date-time" },
+                                    "removed": { "type": "string", "format": "date-time" }
+                                }
+                            }
+                        }
+                    },
+                    "additionalProperties": true
+                }
+            }
+        })
+    }
+}
+
+/// Version of a raw music file
+fn version_of(value: &serde_json::Value) -> Option<u64> {
+    match value {
+        serde_json::Value::Array(_) => Some(1),
+        serde_json::Value::Object(object) => object.get("schema_version")?.as_u64(),
+        _ => None,
+    }
+}
+
+/// Migrate a raw music file to the current version, one version at a time
+/// # Errors
+/// Error if the version is unknown
+fn migrate(mut value: serde_json::Value) -> Result<serde_json::Value, serde_json::Error> {
+    use serde::de::Error;
+    loop {
+        let version = version_of(&value)
+            .ok_or_else(|| serde_json::Error::custom("unknown music file format"))?;
+        value = match version {
+            SCHEMA_VERSION => return Ok(value),
+            1 => serde_json::json!({
+                "schema_version": 2,
+                "musics": value,
+            }),
+            version => {
+                return Err(serde_json::Error::custom(format!(
+                    "music file version {} is not supported (latest is {}), update music-exporter",
+                    version, SCHEMA_VERSION
+                )))
+            }
+        };
+        log::info!("Music file migrated from version {}", version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the migration of the flat array format
+    /// # Panics
+    /// If the assertion fails
+    #[test]
+    fn test_migrate_v1() {
+        let content = r#"[{"author": "a", "title": "one"}]"#;
+        let music_file = MusicFile::parse(content).unwrap();
+        assert_eq!(music_file.schema_version, SCHEMA_VERSION);
+        assert_eq!(music_file.musics.len(), 1);
+        assert!(music_file.generator.is_none());
+
+        let content = r#"{"schema_version": 99, "musics": []}"#;
+        assert!(MusicFile::parse(content).is_err());
+    }
+
+    /// Test that the schema describes every field of a music
+    /// # Panics
+    /// If the assertion fails
+    #[test]
+    fn test_schema_fields() {
+        let text = || Some("text".to_string());
+        let source = crate::music::TrackSource {
+            url: text(),
+            first_seen: text(),
+            last_seen: text(),
+            removed: text(),
+        };
+        // no `..Default::default()`, so a new field must be added here
+        let music = Music {
+            author: "author".to_string(),
+            title: "title".to_string(),
+            url: text(),
+            thumbnail: text(),
+            date: text(),
+            album: text(),
+            original_title: text(),
+            original_author: text(),
+            base_title: text(),
+            version: text(),
+            tags: Some(vec!["tag".to_string()]),
+            rating: Some(5),
+            notes: text(),
+            first_seen: text(),
+            last_seen: text(),
+            sources: [(PlatformType::Deezer, source)].into(),
+            extra: Default::default(),
+        };
+        let schema = MusicFile::json_schema();
+        let properties = &schema["$defs"]["music"]["properties"];
+        let value = serde_json::to_value(&music).unwrap();
+        for key in value.as_object().unwrap().keys() {
+            assert!(
+                properties.get(key).is_some(),
+                "{} is not in the schema",
+                key
+            );
+        }
+        let source_properties = &properties["sources"]["additionalProperties"]["properties"];
+        for key in value["sources"]["deezer"].as_object().unwrap().keys() {
+            assert!(
+                source_properties.get(key).is_some(),
+                "sources.{} is not in the schema",
+                key
+            );
+        }
+    }
+
+    /// Test that the metadata only changes with the musics
+    /// # Panics
+    /// If the assertion fails
+    #[test]
+    fn test_update() {
+        let musics = vec![Music {
+            author: "a".to_string(),
+            title: "one".to_string(),
+            ..Default::default()
+        }];
+        let mut music_file = MusicFile::new(musics.clone(), vec![]);
+        music_file.exported_at = Some("2020-01-01T00:00:00Z".to_string());
+        music_file.update(musics.clone(), &[PlatformType::Deezer]);
+        assert_eq!(music_file.sources, vec![PlatformType::Deezer]);
+        assert_eq!(
+            music_file.exported_at.as_deref(),
+            Some("2020-01-01T00:00:00Z")
+        );
+
+        let mut changed = musics;
+        changed[0].rating = Some(3);
+        music_file.update(changed, &[PlatformType::Deezer]);
+        assert_eq!(music_file.sources, vec![PlatformType::Deezer]);
+        assert_ne!(
+            music_file.exported_at.as_deref(),
+            Some("2020-01-01T00:00:00Z")
+        );
+        assert_eq!(music_file.musics[0].rating, Some(3));
+    }
+}
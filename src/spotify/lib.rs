@@ -1,16 +1,17 @@
 //! Spotify platform implementation
 //! Useful link https://developer.spotify.com/documentation/web-api
 
-use reqwest::Client;
 use std::{future::Future, pin::Pin};
 
 use super::types::{PlaylistItems, SpotifyAccessToken};
 use crate::{
     custom_env,
     errors::MusicExporterError,
+    http::{read_token, HttpClient},
     oauth::listen_for_code,
-    utils::{input_env, to_base_64},
-    Music, MusicExporter, Platform,
+    pagination::{Cursor, Page, Paginator},
+    utils::{prompt_env, to_base_64},
+    Music, MusicExporter, Platform, PlatformType,
 };
 
 /// Spotify platform
@@ -18,6 +19,9 @@ use crate::{
 pub struct SpotifyPlatform {
     /// Authorization token
     authorization: String,
+
+    /// HTTP client
+    http: HttpClient,
 }
 
 /// Spotify redirect URI
@@ -30,6 +34,7 @@ impl SpotifyPlatform {
     /// # Errors
     /// If the request fails
     async fn code_to_token(
+        http: &HttpClient,
         id_client: &str,
         id_client_secret: &str,
         code: &str,
@@ -38,7 +43,7 @@ impl SpotifyPlatform {
             "Basic {}",
             to_base_64(&format!("{}:{}", id_client, id_client_secret))
         );
-        let resp = Client::new()
+        let resp = http
             .post("https://accounts.spotify.com/api/token")
             .header("Accept", "application/json")
             .header("Content-Type", "application/x-www-form-urlencoded")
@@ -50,17 +55,14 @@ impl SpotifyPlatform {
             ])
             .send()
             .await?;
-        let json_response = resp.json::<SpotifyAccessToken>().await?;
+        let json_response = read_token::<SpotifyAccessToken>(PlatformType::Spotify, resp).await?;
         Ok(json_response.access_token)
     }
 
     /// Get the playlist items
     /// # Errors
     /// If the request fails
-    async fn get_playlist_items(
-        &self,
-        offset: Option<u64>,
-    ) -> Result<(Vec<Music>, Option<u64>), MusicExporterError> {
+    async fn get_playlist_items(&self, offset: Option<u64>) -> Result<Page, MusicExporterError> {
         let url = url::Url::parse_with_params(
             "https://api.spotify.com/v1/me/tracks",
             &[
@@ -68,24 +70,21 @@ impl SpotifyPlatform {
                 ("offset", offset.unwrap_or(0).to_string()),
             ],
         )?;
-        let resp = Client::new()
+        let request = self
+            .http
             .get(url)
             .header("Authorization", format!("Bearer {}", &self.authorization))
-            .header("Accept", "application/json")
-            .send()
-            .await?;
-        let json_response = match resp.status() {
-            reqwest::StatusCode::OK => {
-                // on success, parse our JSON to an APIResponse
-                resp.json::<PlaylistItems>().await?
-            }
-            err => {
-                return Err(MusicExporterError::new(format!(
-                    "Failed to get response for the playlist items {}",
-                    err
-                )))
-            }
-        };
+            .header("Accept", "application/json");
+        let json_response = self
+            .http
+            .fetch_json::<PlaylistItems>(PlatformType::Spotify, request)
+            .await
+            .map_err(|e| {
+                MusicExporterError::new_with_source(
+                    "Failed to get response for the playlist items",
+                    e,
+                )
+            })?;
         let items = json_response
             .items
             .iter()
@@ -96,6 +95,7 @@ impl SpotifyPlatform {
                 url: Some(item.track.external_urls.spotify.clone()),
                 date: Some(item.track.album.release_date.clone()),
                 album: Some(item.track.album.name.clone()),
+                ..Default::default()
             })
             .collect();
         let current_offset = json_response.offset;
@@ -105,23 +105,32 @@ impl SpotifyPlatform {
             None
         };
         log::info!("Next offset: {:?}", next_offset);
-        Ok((items, next_offset))
+        Ok(Page {
+            items,
+            next: next_offset.map(Cursor::Offset),
+            total: Some(json_response.total),
+        })
     }
 }
 
 impl Platform for SpotifyPlatform {
     fn try_new(
-        _music_exp: &MusicExporter,
+        music_exp: &MusicExporter,
     ) -> Pin<Box<dyn Future<Output = Result<Self, MusicExporterError>> + Send>> {
-        Box::pin(async {
-            let id_client = input_env(
+        let http = music_exp.http_client();
+        let cancel = music_exp.cancellation_token();
+        Box::pin(async move {
+            let http = http?;
+            let id_client = prompt_env(
                 "Please enter the spotify developper app 'id_client'",
                 custom_env!("SPOTIFY_ID_CLIENT"),
-            )?;
-            let id_client_secret = input_env(
+            )
+            .await?;
+            let id_client_secret = prompt_env(
                 "Please enter the spotify developper app 'id_client_secret'",
                 custom_env!("SPOTIFY_ID_CLIENT_SECRET"),
-            )?;
+            )
+            .await?;
             let url_oauth = url::Url::parse_with_params(
                 "https://accounts.spotify.com/authorize",
                 &[
@@ -135,38 +144,41 @@ impl Platform for SpotifyPlatform {
                 ],
             )?;
             // start the server in a thread
-            let srv = listen_for_code(8000);
+            let srv = listen_for_code(8000, cancel);
             println!(
                 "Please go to this url to get the authorization token (or hit CTRCL+C): {}",
                 url_oauth
             );
             match srv.await {
                 Ok(resp) => {
-                    let authorization =
-                        SpotifyPlatform::code_to_token(&id_client, &id_client_secret, &resp.code)
-                            .await?;
-                    Ok(Self { authorization })
+                    let authorization = SpotifyPlatform::code_to_token(
+                        &http,
+                        &id_client,
+                        &id_client_secret,
+                        &resp.code,
+                    )
+                    .await?;
+                    Ok(Self {
+                        authorization,
+                        http,
+                    })
                 }
-                Err(_) => Err(MusicExporterError::new("Failed to get the code")),
+                Err(e) => Err(MusicExporterError::new_with_source(
+                    "Failed to get the code",
+                    e,
+                )),
             }
         })
     }
 
     fn get_list<'a>(
         &'a self,
+        paginator: &'a Paginator,
     ) -> Pin<Box<dyn Future<Output = Result<Vec<Music>, MusicExporterError>> + Send + 'a>> {
         Box::pin(async {
-            let mut items = Vec::new();
-            let mut page_next = None;
-            loop {
-                let (new_items, next_page) = self.get_playlist_items(page_next).await?;
-                items.extend(new_items);
-                page_next = next_page;
-                if page_next.is_none() {
-                    break;
-                }
-            }
-            Ok(items)
+            paginator
+                .collect(|cursor| self.get_playlist_items(cursor.and_then(|c| c.offset())))
+                .await
         })
     }
 }
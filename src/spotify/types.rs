@@ -7,12 +7,14 @@ pub struct SpotifyArtist {
     pub name: String,
 }
 
+/// Spotify Image
 #[derive(Debug, serde::Deserialize)]
 pub struct SpotifyImage {
     /// Image url
     pub url: String,
 }
 
+/// Spotify Album
 #[derive(Debug, serde::Deserialize)]
 pub struct SpotifyAlbum {
     /// Album release date
@@ -23,6 +25,7 @@ pub struct SpotifyAlbum {
     pub name: String,
 }
 
+/// Spotify Track
 #[derive(Debug, serde::Deserialize)]
 pub struct TrackPlaylistItem {
     /// Track name
@@ -37,18 +40,21 @@ pub struct TrackPlaylistItem {
     /// Track external urls
     pub external_urls: SpotifyExternalUrls,
 }
+/// Spotify External urls
 #[derive(Debug, serde::Deserialize)]
 pub struct SpotifyExternalUrls {
     /// Spotify url
     pub spotify: String,
 }
 
+/// Spotify Playlist item
 #[derive(Debug, serde::Deserialize)]
 pub struct PlaylistItem {
     /// Track item
     pub track: TrackPlaylistItem,
 }
 
+/// Spotify Playlist items
 #[derive(Debug, serde::Deserialize)]
 pub struct PlaylistItems {
     /// Next page url
@@ -57,10 +63,14 @@ pub struct PlaylistItems {
     /// Offset
     pub offset: u64,
 
+    /// Total number of items
+    pub total: u64,
+
     /// Playlist items
     pub items: Vec<PlaylistItem>,
 }
 
+/// Spotify Access token
 #[derive(Debug, serde::Deserialize)]
 pub struct SpotifyAccessToken {
     /// Access token
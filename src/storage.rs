@@ -0,0 +1,280 @@
+//! Safe storage of the music file
+
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    errors::{ErrorKind, MusicExporterError},
+    Music,
+};
+
+/// Path next to `path`, with `suffix` appended to its file name
+pub(crate) fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name: OsString = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+/// Number of temporary files created by this process
+static TMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Write a file atomically: the data is written to a temporary file
+/// that then replaces `path`, so a crash never leaves a truncated file
+///
+/// The temporary file is unique, so concurrent writers never share it
+/// # Errors
+/// Error if the temporary file cannot be written or renamed
+pub(crate) fn write_atomic<F>(path: &Path, write: F) -> Result<(), MusicExporterError>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), MusicExporterError>,
+{
+    let suffix = format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        TMP_FILES.fetch_add(1, Ordering::Relaxed)
+    );
+    let tmp_path = with_suffix(path, &suffix);
+    let res = File::create(&tmp_path)
+        .map_err(MusicExporterError::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            writer.flush()?;
+            let file = writer.into_inner().map_err(|e| e.into_error())?;
+            file.sync_all()?;
+            Ok(())
+        })
+        .and_then(|_| Ok(fs::rename(&tmp_path, path)?));
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    res.map_err(|e| {
+        MusicExporterError::new_with_source(format!("Cannot write {}", path.display()), e)
+    })
+}
+
+/// Keep `count` rotated backups of `path` (`path.1` being the most recent)
+/// # Errors
+/// Error if a backup cannot be moved or copied
+pub(crate) fn rotate_backups(path: &Path, count: usize) -> Result<(), MusicExporterError> {
+    if count == 0 || !path.exists() {
+        return Ok(());
+    }
+    let backup = |idx: usize| with_suffix(path, &format!(".{}", idx));
+    for idx in (1..count).rev() {
+        if backup(idx).exists() {
+            fs::rename(backup(idx), backup(idx + 1))?;
+        }
+    }
+    // copy, so the music file is never missing
+    fs::copy(path, backup(1))?;
+    Ok(())
+}
+
+/// Advisory lock on the music file, released and removed when dropped
+#[derive(Debug)]
+pub(crate) struct FileLock {
+    /// Locked file
+    _file: File,
+
+    /// Path of the lock file
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// Lock `path` through a `.lock` file next to it
+    /// # Errors
+    /// Error if another run holds the lock
+    pub(crate) fn try_lock(path: &Path) -> Result<Self, MusicExporterError> {
+        let lock_path = with_suffix(path, ".lock");
+        if let Some(parent) = lock_path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)?;
+        let used = || {
+            MusicExporterError::new(format!(
+                "{} is used by another run (lock {})",
+                path.display(),
+                lock_path.display()
+            ))
+            .with_kind(ErrorKind::Io)
+        };
+        match file.try_lock() {
+            // the lock file may have been removed by the run releasing it
+            Ok(()) if is_same_file(&file, &lock_path) => Ok(Self {
+                _file: file,
+                path: lock_path,
+            }),
+            Ok(()) | Err(TryLockError::WouldBlock) => Err(used()),
+            Err(TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // removed while still locked, so no other run can lock the removed file
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Whether `file` is still the file found at `path`
+fn is_same_file(file: &File, path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (file.metadata(), fs::metadata(path)) {
+            (Ok(opened), Ok(current)) => {
+                opened.dev() == current.dev() && opened.ino() == current.ino()
+            }
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        // an open file cannot be removed
+        let _ = file;
+        path.exists()
+    }
+}
+
+/// Salvage the valid musics of a music file that failed to parse
+///
+/// Returns the musics and the number of invalid entries skipped
+pub(crate) fn recover_musics(content: &str) -> (Vec<Music>, usize) {
+    let entries = match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(serde_json::Value::Array(values)) => values,
+        Ok(serde_json::Value::Object(mut object)) => match object.remove("musics") {
+            Some(serde_json::Value::Array(values)) => values,
+            _ => vec![],
+        },
+        Ok(value) => vec![value],
+        // syntax error: parse every top-level object on its own
+        Err(_) => top_level_objects(content)
+            .into_iter()
+            .map(|object| {
+                serde_json::from_str::<serde_json::Value>(object).unwrap_or(serde_json::Value::Null)
+            })
+            .collect(),
+    };
+    let mut skipped = 0;
+    let musics = entries
+        .into_iter()
+        .filter_map(
+            |entry| match serde_json::from_value::<Music>(entry.clone()) {
+                Ok(music) => Some(music),
+                Err(e) => {
+                    log::warn!("Skipping invalid entry ({}): {}", e, entry);
+                    skipped += 1;
+                    None
+                }
+            },
+        )
+        .collect();
+    (musics, skipped)
+}
+
+/// Text of the objects found at the first level of the musics array
+fn top_level_objects(content: &str) -> Vec<&str> {
+    // skip the envelope of the music file
+    let content = match content.find("\"musics\"") {
+        Some(idx) if content.trim_start().starts_with('{') => &content[idx..],
+        _ => content,
+    };
+    let mut objects = vec![];
+    let mut depth = 0usize;
+    let mut start = None;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (idx, c) in content.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => {
+                if depth == 0 {
+                    start = Some(idx);
+                }
+                depth += 1;
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    if let Some(start) = start.take() {
+                        objects.push(&content[start..=idx]);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    objects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the lock of the music file
+    /// # Panics
+    /// If the assertion fails
+    #[test]
+    fn test_file_lock() {
+        let path =
+            std::env::temp_dir().join(format!("music-exporter-{}-lock.json", std::process::id()));
+        let lock_path = with_suffix(&path, ".lock");
+        let lock = FileLock::try_lock(&path).unwrap();
+        assert!(lock_path.exists());
+        let err = FileLock::try_lock(&path).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Io);
+        drop(lock);
+        assert!(!lock_path.exists());
+        drop(FileLock::try_lock(&path).unwrap());
+    }
+
+    /// Test the recovery of a broken music file
+    /// # Panics
+    /// If the assertion fails
+    #[test]
+    fn test_recover_musics() {
+        let content = r#"[
+            {"author": "a", "title": "one", "url": null, "thumbnail": null, "date": null, "album": null},
+            {"author": "b", "title": "two" "url": null},
+            {"author": "c", "title": "th{r}ee \" }", "url": null, "thumbnail": null, "date": null, "album": null},
+        "#;
+        let (musics, skipped) = recover_musics(content);
+        let titles = musics.iter().map(|m| m.title.as_str()).collect::<Vec<_>>();
+        assert_eq!(titles, vec!["one", "th{r}ee \" }"]);
+        assert_eq!(skipped, 1);
+
+        let content = r#"[{"author": "a", "title": "one"}, {"title": "no author"}]"#;
+        let (musics, skipped) = recover_musics(content);
+        assert_eq!(musics.len(), 1);
+        assert_eq!(skipped, 1);
+
+        let content = r#"{"schema_version": 2, "sources": ["deezer"], "musics": [
+            {"author": "a", "title": "one"},
+            {"author": "b", "title": "two"}"#;
+        let (musics, skipped) = recover_musics(content);
+        assert_eq!(musics.len(), 2);
+        assert_eq!(skipped, 0);
+    }
+}
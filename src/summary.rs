@@ -0,0 +1,89 @@
+//! Summary of an export run
+
+use crate::{
+    errors::{ErrorKind, MusicExporterError},
+    PlatformType,
+};
+
+/// Outcome of the export of a platform
+#[derive(Debug, Clone)]
+pub struct PlatformOutcome {
+    /// Platform exported
+    pub platform: PlatformType,
+
+    /// Number of musics retrieved
+    pub count: usize,
+
+    /// Error, if the export failed
+    pub error: Option<MusicExporterError>,
+
+    /// URLs of the musics left out on purpose by the platform
+    pub skipped: Vec<String>,
+}
+
+/// Summary of an export run
+#[derive(Debug, Clone, Default)]
+pub struct RunSummary {
+    /// Outcome of each platform, in the order of the platforms
+    pub platforms: Vec<PlatformOutcome>,
+}
+
+impl RunSummary {
+    /// Platforms that failed
+    pub fn failures(&self) -> impl Iterator<Item = &PlatformOutcome> {
+        self.platforms
+            .iter()
+            .filter(|outcome| outcome.error.is_some())
+    }
+
+    /// Whether every platform failed, so there is nothing to save
+    pub fn all_failed(&self) -> bool {
+        !self.platforms.is_empty() && self.failures().count() == self.platforms.len()
+    }
+
+    /// Log the outcome of each platform
+    pub fn log(&self) {
+        for outcome in &self.platforms {
+            match &outcome.error {
+                Some(error) => log::error!("{}: failed - {}", outcome.platform, error),
+                None => log::info!("{}: {} items", outcome.platform, outcome.count),
+            }
+        }
+    }
+
+    /// Check that every platform succeeded
+    /// # Errors
+    /// Partial failure error if at least one platform failed,
+    /// or the error of the first platform if all of them failed
+    pub fn check(&self) -> Result<(), MusicExporterError> {
+        let failures = self.failures().collect::<Vec<_>>();
+        let Some(first) = failures.first() else {
+            return Ok(());
+        };
+        let names = failures
+            .iter()
+            .map(|outcome| outcome.platform.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let all_failed = self.all_failed();
+        let message = if all_failed {
+            format!("Export failed: all platforms failed ({})", names)
+        } else {
+            format!(
+                "Partial export: {} of {} platforms failed ({})",
+                failures.len(),
+                self.platforms.len(),
+                names
+            )
+        };
+        let error = match &first.error {
+            Some(error) => MusicExporterError::new_with_source(message, error.clone()),
+            None => MusicExporterError::new(message),
+        };
+        if all_failed {
+            Err(error)
+        } else {
+            Err(error.with_kind(ErrorKind::PartialFailure))
+        }
+    }
+}
@@ -1,18 +1,36 @@
 //! Utility functions
 
-use clap::{ArgAction, Parser, ValueEnum};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use futures_util::future::join_all;
 use serde::Serialize;
 use std::{
-    fs::{self, File, OpenOptions},
+    collections::{HashMap, HashSet},
+    fs,
     future::Future,
-    io::{BufReader, BufWriter, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     pin::Pin,
+    sync::{Arc, OnceLock},
+    time::Duration,
 };
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    errors::MusicExporterError, music, DeezerPlatform, Music, SpotifyPlatform, YoutubePlatform,
+    diff::{DiffFormat, MusicDiff},
+    errors::{ErrorKind, MusicExporterError},
+    http::{HttpClient, RetryPolicy},
+    ignore::IgnoreList,
+    music::{self, DedupOptions, MergePolicy, TrackSource},
+    overrides::Overrides,
+    pagination::Paginator,
+    rules::CleaningRules,
+    schema::MusicFile,
+    storage::{self, FileLock},
+    summary::{PlatformOutcome, RunSummary},
+    youtube::title::split_music,
+    DeezerPlatform, Music, SpotifyPlatform, YoutubePlatform,
 };
+#[cfg(feature = "history")]
+use crate::{History, HistoryQuery};
 
 /// Platform trait
 pub trait Platform: Send + Sync {
@@ -23,14 +41,37 @@ pub trait Platform: Send + Sync {
     where
         Self: Sized;
 
-    /// Get the list of music
+    /// Get the list of music, using the paginator to fetch the pages
+    ///
+    /// The titles and authors are the ones of the platform,
+    /// the cleaning rules are applied by the export
     fn get_list<'a>(
         &'a self,
+        paginator: &'a Paginator,
     ) -> Pin<Box<dyn Future<Output = Result<Vec<Music>, MusicExporterError>> + Send + 'a>>;
+
+    /// URLs of the musics of the list left out on purpose by the last `get_list`
+    /// (like the Youtube videos outside the Music category), so they are not marked as removed
+    fn skipped(&self) -> Vec<String> {
+        vec![]
+    }
 }
 
 /// Platform type
-#[derive(Debug, Clone, ValueEnum)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    ValueEnum,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
 #[non_exhaustive]
 pub enum PlatformType {
     /// Deezer platform
@@ -71,29 +112,94 @@ impl PlatformType {
     }
 }
 
+/// Commands of the CLI (exporting when none is given)
+#[derive(Debug, Clone, Subcommand)]
+#[non_exhaustive]
+pub enum Command {
+    /// Print the JSON Schema of the music file
+    Schema,
+
+    /// Print the musics added, removed and changed between two music files
+    Diff {
+        /// Music file to compare from [default: the music file]
+        old: Option<PathBuf>,
+
+        /// Music file to compare to [default: the musics of the platforms, fetched without writing]
+        new: Option<PathBuf>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t)]
+        format: DiffFormat,
+    },
+
+    /// Query the history database
+    #[cfg(feature = "history")]
+    History {
+        /// Query to run
+        #[command(subcommand)]
+        query: HistoryQuery,
+    },
+}
+
 /// Music-exporter args
 #[derive(Debug, Parser)]
 #[command(
     name = "music-exporter",
-    about = "Exports music files for given platforms"
+    about = "Exports music files for given platforms",
+    subcommand_negates_reqs = true
 )]
 pub struct MusicExporter {
+    /// Command to run instead of exporting
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Path to optional .env file
     #[arg(long, value_name = "ENV_FILE", required = false)]
     pub env_file: Option<PathBuf>,
 
     /// Path to the music file
-    #[arg(long, value_name = "MUSIC_FILE", required = true)]
+    // the default value is only used by the commands, exporting requires the argument
+    #[arg(
+        long,
+        value_name = "MUSIC_FILE",
+        required = true,
+        default_value = "musics.json",
+        hide_default_value = true
+    )]
     pub music_file: PathBuf,
 
-    /// Remove duplicates
+    /// Keep the duplicates instead of merging them
     #[arg(long, action=ArgAction::SetFalse)]
     pub remove_duplicates: bool,
 
-    /// Sort musics
+    /// Keep the musics in the order of the platforms instead of sorting them
     #[arg(long, action=ArgAction::SetFalse)]
     pub sort: bool,
 
+    /// JSON file of rules cleaning the titles and authors, see the README
+    #[arg(long, value_name = "RULES_FILE")]
+    pub rules_file: Option<PathBuf>,
+
+    /// JSON file of artist aliases and corrections of the musics, see the README
+    #[arg(long, value_name = "OVERRIDES_FILE")]
+    pub overrides_file: Option<PathBuf>,
+
+    /// JSON file of the ignored musics, artists, channels and titles, see the README
+    #[arg(long, value_name = "IGNORE_FILE")]
+    pub ignore_file: Option<PathBuf>,
+
+    /// Also remove the musics of an author whose titles are this similar (between 0 and 1, 0.9 is a good start)
+    #[arg(long, value_name = "THRESHOLD", value_parser = parse_similarity)]
+    pub similarity: Option<f64>,
+
+    /// Also remove the other versions of a title (live, remaster, radio edit...)
+    #[arg(long)]
+    pub merge_versions: bool,
+
+    /// How the musics fetched from the platforms update the ones already in the music file
+    #[arg(long, value_enum, default_value_t = MergePolicy::FillMissing)]
+    pub merge_policy: MergePolicy,
+
     /// Target platforms (must provide at least one)
     #[arg(long = "platform", value_enum, required = true, num_args = 1..)]
     pub platforms: Vec<PlatformType>,
@@ -101,6 +207,91 @@ pub struct MusicExporter {
     /// Custom youtube playlist id
     #[arg(long, visible_alias = "ytb-playlist-id")]
     pub youtube_playlist_id: Option<String>,
+
+    /// Keep the titles of the Youtube videos instead of splitting their "Artist - Title"
+    #[arg(long)]
+    pub keep_youtube_titles: bool,
+
+    /// Only keep the Youtube videos of the Music category
+    #[arg(long)]
+    pub youtube_music_only: bool,
+
+    /// Maximum number of retries of a failed request
+    #[arg(long, value_name = "RETRIES", default_value_t = 3)]
+    pub max_retries: u32,
+
+    /// Base delay in milliseconds of the exponential backoff between retries
+    #[arg(long, value_name = "MILLISECONDS", default_value_t = 1000)]
+    pub retry_delay: u64,
+
+    /// Stop fetching a platform once a page only contains musics already in the music file
+    #[arg(long)]
+    pub incremental: bool,
+
+    /// Continue an interrupted export from its checkpoint
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Save the musics of the platforms that succeeded even if others failed
+    ///
+    /// Nothing is saved when all the platforms failed
+    #[arg(long)]
+    pub best_effort: bool,
+
+    /// Maximum number of pages fetched at the same time for offset-paginated platforms
+    #[arg(long, value_name = "PAGES", default_value_t = 4)]
+    pub page_concurrency: usize,
+
+    /// Timeout in seconds of a request
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    pub timeout: u64,
+
+    /// HTTP(S) proxy used for all the requests
+    #[arg(long, value_name = "PROXY_URL")]
+    pub proxy: Option<String>,
+
+    /// Extra root certificate (PEM) to trust, can be repeated
+    #[arg(long, value_name = "PEM_FILE")]
+    pub ca_cert: Vec<PathBuf>,
+
+    /// User agent of the requests
+    #[arg(long, value_name = "USER_AGENT")]
+    pub user_agent: Option<String>,
+
+    /// Keep the valid entries of a music file that failed to parse (the others are dropped)
+    #[arg(long)]
+    pub recover: bool,
+
+    /// Number of rotated backups of the music file to keep
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    pub backups: usize,
+
+    /// Write the musics fetched so far when the export is cancelled with CTRL+C
+    #[arg(long)]
+    pub save_on_cancel: bool,
+
+    /// SQLite database recording the musics of every run
+    #[cfg(feature = "history")]
+    #[arg(long, value_name = "DB_FILE")]
+    pub history_db: Option<PathBuf>,
+
+    /// HTTP client shared by the platforms
+    #[arg(skip)]
+    http_client: OnceLock<HttpClient>,
+
+    /// Cancellation of the export
+    #[arg(skip)]
+    cancel: CancellationToken,
+}
+
+/// Parse a similarity threshold, between 0 and 1
+/// # Errors
+/// Error if the threshold is not a number between 0 and 1
+fn parse_similarity(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(threshold) if (0.0..=1.0).contains(&threshold) => Ok(threshold),
+        _ => Err(format!("{} is not a number between 0 and 1", value)),
+    }
 }
 
 /// Main function for the CLI
@@ -123,36 +314,238 @@ impl MusicExporter {
         platforms: &[PlatformType],
     ) -> Self {
         Self {
+            command: None,
             music_file,
             env_file: env_path,
             platforms: platforms.to_vec(),
             youtube_playlist_id: None,
+            keep_youtube_titles: false,
+            youtube_music_only: false,
             remove_duplicates: true,
             sort: true,
+            rules_file: None,
+            overrides_file: None,
+            ignore_file: None,
+            similarity: None,
+            merge_versions: false,
+            merge_policy: MergePolicy::FillMissing,
+            max_retries: 3,
+            retry_delay: 1000,
+            incremental: false,
+            resume: false,
+            best_effort: false,
+            page_concurrency: 4,
+            timeout: 30,
+            proxy: None,
+            ca_cert: vec![],
+            user_agent: None,
+            recover: false,
+            backups: 0,
+            save_on_cancel: false,
+            #[cfg(feature = "history")]
+            history_db: None,
+            http_client: OnceLock::new(),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Load the rules, overrides and ignore files, before any request
+    /// # Errors
+    /// Error if one of the files cannot be loaded
+    fn corrections(&self) -> Result<Corrections, MusicExporterError> {
+        let rules = match &self.rules_file {
+            Some(path) => CleaningRules::from_file(path)?,
+            None => CleaningRules::default(),
+        };
+        let overrides = self
+            .overrides_file
+            .as_deref()
+            .map(Overrides::from_file)
+            .transpose()?;
+        let ignore = match &self.ignore_file {
+            Some(path) => IgnoreList::from_file(path)?,
+            None => IgnoreList::default(),
+        };
+        Ok(Corrections {
+            rules,
+            overrides,
+            ignore,
+        })
+    }
+
+    /// Split and clean the Youtube videos of the music file exported before their
+    /// titles were split, since merging never replaces their titles
+    fn split_youtube_titles(&self, rules: &CleaningRules, musics: &mut [Music]) {
+        if self.keep_youtube_titles {
+            return;
+        }
+        let mut split = 0;
+        for music in musics.iter_mut().filter(|music| {
+            let is_video = music.sources.contains_key(&PlatformType::Youtube)
+                || music
+                    .url
+                    .as_ref()
+                    .is_some_and(|url| url.starts_with("https://www.youtube.com/watch"));
+            is_video && music.original_title.is_none() && music.original_author.is_none()
+        }) {
+            let before = (music.title.clone(), music.author.clone());
+            split_music(music);
+            rules.clean(PlatformType::Youtube, music);
+            if (&music.title, &music.author) != (&before.0, &before.1) {
+                split += 1;
+            }
+        }
+        if split > 0 {
+            log::info!("Split Youtube titles of the music file: {}", split);
+        }
+    }
+
+    /// Options of the removal of the duplicates
+    fn dedup_options(&self) -> DedupOptions {
+        DedupOptions {
+            similarity: self.similarity,
+            merge_versions: self.merge_versions,
+        }
+    }
+
+    /// Token cancelling the export (triggered by CTRL+C in [`MusicExporter::run_main`])
+    pub(crate) fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Stop the running export, like CTRL+C in [`MusicExporter::run_main`]
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// HTTP client shared by the platforms, built on first use
+    /// # Errors
+    /// Error if the proxy or a certificate is invalid
+    pub(crate) fn http_client(&self) -> Result<HttpClient, MusicExporterError> {
+        if let Some(http_client) = self.http_client.get() {
+            return Ok(http_client.clone());
+        }
+        let user_agent = match &self.user_agent {
+            Some(user_agent) => user_agent.clone(),
+            None => format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        };
+        let mut builder = reqwest::Client::builder()
+            .user_agent(user_agent)
+            .timeout(Duration::from_secs(self.timeout));
+        if let Some(proxy) = &self.proxy {
+            let proxy = reqwest::Proxy::all(proxy).map_err(|e| {
+                MusicExporterError::new_with_source(format!("Invalid proxy {}", proxy), e.into())
+                    .with_kind(ErrorKind::Config)
+            })?;
+            builder = builder.proxy(proxy);
         }
+        for path in &self.ca_cert {
+            let certificate = fs::read(path)
+                .map_err(MusicExporterError::from)
+                .and_then(|pem| Ok(reqwest::Certificate::from_pem(&pem)?))
+                .map_err(|e| {
+                    MusicExporterError::new_with_source(
+                        format!("Invalid certificate {}", path.display()),
+                        e,
+                    )
+                    .with_kind(ErrorKind::Config)
+                })?;
+            builder = builder.add_root_certificate(certificate);
+        }
+        let retry = RetryPolicy {
+            max_retries: self.max_retries,
+            base_delay: Duration::from_millis(self.retry_delay),
+            ..Default::default()
+        };
+        let http_client = HttpClient::new(builder.build()?, retry);
+        Ok(self.http_client.get_or_init(|| http_client).clone())
     }
 
     /// Get all the musics
     /// # Errors
     /// Fails on request
     pub async fn get_musics(&self) -> Result<Vec<Music>, MusicExporterError> {
-        let mut items = self.read_from_file()?;
-        let musics_from_platforms = self.get_music_from_platforms().await?;
-        items.extend(musics_from_platforms);
+        let (items, _summary) = self.get_musics_with_summary().await?;
+        Ok(items)
+    }
+
+    /// Get all the musics and the outcome of each platform
+    /// # Errors
+    /// Fails on request (only on the first platform failure if not in best-effort mode)
+    pub async fn get_musics_with_summary(
+        &self,
+    ) -> Result<(Vec<Music>, RunSummary), MusicExporterError> {
+        let items = self.read_from_file()?;
+        self.collect_musics(items).await
+    }
+
+    /// Merge the musics of the music file with the musics of the platforms
+    /// # Errors
+    /// Fails on request (only on the first platform failure if not in best-effort mode)
+    async fn collect_musics(
+        &self,
+        mut items: Vec<Music>,
+    ) -> Result<(Vec<Music>, RunSummary), MusicExporterError> {
+        let corrections = self.corrections()?;
+        self.prepare_file_musics(&corrections, &mut items);
+        let (mut musics_from_platforms, summary) =
+            self.export_platforms(&items, &corrections, true).await?;
+        // before the ignore list, so the ignored musics are not marked as removed
+        let mut fetched = HashMap::<_, HashSet<_>>::new();
+        for music in &musics_from_platforms {
+            for (platform, source) in &music.sources {
+                fetched
+                    .entry(*platform)
+                    .or_default()
+                    .extend(source.url.clone());
+            }
+        }
+        for outcome in &summary.platforms {
+            fetched
+                .entry(outcome.platform)
+                .or_default()
+                .extend(outcome.skipped.iter().cloned());
+        }
+        corrections.apply(&mut musics_from_platforms);
         // write to file
-        log::info!("Total items: {}", items.len());
+        log::info!("Total items: {}", items.len() + musics_from_platforms.len());
         let mut items = if self.remove_duplicates {
-            music::unique_music(items)
+            music::merge_music(
+                items,
+                musics_from_platforms,
+                self.merge_policy,
+                self.dedup_options(),
+            )
         } else {
+            items.extend(musics_from_platforms);
             items
         };
+        // a partial export cannot tell which musics are gone
+        if !self.incremental && !self.cancel.is_cancelled() {
+            let now = jiff::Timestamp::now().to_string();
+            for outcome in summary.platforms.iter().filter(|o| o.error.is_none()) {
+                let fetched = fetched.remove(&outcome.platform).unwrap_or_default();
+                let removed = music::mark_removed(&mut items, outcome.platform, &fetched, &now);
+                log::info!("{}: {} removed items", outcome.platform, removed);
+            }
+        }
         let items = if self.sort {
             items.sort();
             items
         } else {
             items
         };
-        Ok(items)
+        Ok((items, summary))
+    }
+
+    /// Split the Youtube titles and refresh the versions of the musics of the music file
+    /// (the titles may have been edited), then apply the overrides
+    fn prepare_file_musics(&self, corrections: &Corrections, musics: &mut [Music]) {
+        self.split_youtube_titles(&corrections.rules, musics);
+        for music in musics.iter_mut() {
+            music.refresh_version();
+        }
+        corrections.correct(musics);
     }
 
     /// Load the env file
@@ -161,7 +554,12 @@ impl MusicExporter {
     pub fn load_env(&self) -> Result<(), MusicExporterError> {
         match &self.env_file {
             Some(path) => {
-                dotenv::from_path(path)?;
+                dotenv::from_path(path).map_err(|e| {
+                    MusicExporterError::new_with_source(
+                        format!("Cannot load {}", path.display()),
+                        e.into(),
+                    )
+                })?;
             }
             None => {
                 // dotenv::dotenv()?;
@@ -176,59 +574,340 @@ impl MusicExporter {
     pub async fn run_main(&self) -> Result<Vec<Music>, MusicExporterError> {
         self.load_env()
             .map_err(|e| MusicExporterError::new_with_source("Failed to load env file", e))?;
-        let musics = self.get_musics().await?;
+        let _lock = FileLock::try_lock(&self.music_file)?;
+        #[cfg(feature = "history")]
+        let mut history = self.open_history()?;
+        let mut music_file = self.read_music_file()?;
+        let cancel = self.cancellation_token();
+        let ctrl_c = tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                log::warn!("CTRL+C received, stopping the export");
+                cancel.cancel();
+            }
+        });
+        let res = self.collect_musics(music_file.musics.clone()).await;
+        ctrl_c.abort();
+        let (musics, summary) = res?;
         log::info!("Unique items: {}", musics.len());
-        self.write_to_file(&musics)?;
+        if self.cancel.is_cancelled() {
+            let message = if self.save_on_cancel {
+                music_file.update(musics, &self.platforms);
+                self.write_music_file(&music_file)?;
+                "Export cancelled, the musics fetched so far were saved"
+            } else {
+                "Export cancelled, the music file was left untouched"
+            };
+            return Err(MusicExporterError::new(message).with_kind(ErrorKind::Cancelled));
+        }
+        music_file.update(musics.clone(), &self.platforms);
+        self.write_music_file(&music_file)?;
+        #[cfg(feature = "history")]
+        if let Some(history) = &mut history {
+            history.record_run(jiff::Timestamp::now(), &self.platforms, &musics)?;
+        }
+        summary.log();
+        summary.check()?;
         Ok(musics)
     }
 
+    /// Compare two music files, or a music file with the musics of the platforms
+    ///
+    /// `old` defaults to the music file, and when `new` is missing the platforms
+    /// are exported without writing anything (not even checkpoints)
+    /// # Errors
+    /// Error if a music file cannot be read or the export fails,
+    /// or if an incremental export is asked (it would not fetch the whole lists)
+    pub async fn diff(
+        &self,
+        old: Option<&Path>,
+        new: Option<&Path>,
+    ) -> Result<MusicDiff, MusicExporterError> {
+        let mut old = self
+            .read_music_file_at(old.unwrap_or(&self.music_file))?
+            .musics;
+        let new = match new {
+            Some(path) => self.read_music_file_at(path)?.musics,
+            None => {
+                if self.platforms.is_empty() {
+                    return Err(MusicExporterError::new(
+                        "Give a second music file or at least one --platform to compare with",
+                    )
+                    .with_kind(ErrorKind::Config));
+                }
+                if self.incremental {
+                    return Err(MusicExporterError::new(
+                        "--incremental cannot be used to compare with the platforms",
+                    )
+                    .with_kind(ErrorKind::Config));
+                }
+                let corrections = self.corrections()?;
+                self.load_env().map_err(|e| {
+                    MusicExporterError::new_with_source("Failed to load env file", e)
+                })?;
+                self.prepare_file_musics(&corrections, &mut old);
+                // the ignored musics are kept in the file, but not fetched anymore
+                corrections.ignore.filter(&mut old);
+                let (mut musics, summary) =
+                    self.export_platforms(&old, &corrections, false).await?;
+                let skipped = summary
+                    .platforms
+                    .iter()
+                    .flat_map(|outcome| &outcome.skipped)
+                    .collect::<HashSet<_>>();
+                // only the musics still liked on an exported platform can be removed from it
+                let exported = summary
+                    .platforms
+                    .iter()
+                    .filter(|outcome| outcome.error.is_none())
+                    .map(|outcome| outcome.platform)
+                    .collect::<Vec<_>>();
+                old.retain(|music| {
+                    exported.iter().any(|platform| music.is_liked_on(*platform))
+                        && music.url.as_ref().is_none_or(|url| !skipped.contains(url))
+                });
+                corrections.apply(&mut musics);
+                music::unique_music(musics, self.dedup_options())
+            }
+        };
+        Ok(MusicDiff::new(&old, &new))
+    }
+
+    /// Open the history database, if one is given
+    /// # Errors
+    /// Error if the database cannot be opened
+    #[cfg(feature = "history")]
+    fn open_history(&self) -> Result<Option<History>, MusicExporterError> {
+        self.history_db.as_deref().map(History::open).transpose()
+    }
+
+    /// Run a query on the history database
+    /// # Errors
+    /// Error if no history database is given or the query fails
+    #[cfg(feature = "history")]
+    pub fn query_history(&self, query: &HistoryQuery) -> Result<String, MusicExporterError> {
+        let history = self.open_history()?.ok_or_else(|| {
+            MusicExporterError::new("Give the history database with --history-db")
+                .with_kind(ErrorKind::Config)
+        })?;
+        query.run(&history)
+    }
+
     /// Get the list of music from the selected platforms
+    ///
+    /// Platforms are exported concurrently, but initialized one at a time
+    /// since the prompts and the OAuth flows share stdin and the callback port.
+    /// The musics are merged in the order of the platforms
     /// # Errors
     /// Fails if fail to get lists
     pub async fn get_music_from_platforms(&self) -> Result<Vec<Music>, MusicExporterError> {
+        let existing = if self.incremental {
+            self.read_from_file()?
+        } else {
+            vec![]
+        };
+        let corrections = self.corrections()?;
+        let (mut items, _summary) = self.export_platforms(&existing, &corrections, true).await?;
+        corrections.apply(&mut items);
+        Ok(items)
+    }
+
+    /// Export the selected platforms, `existing` being the musics already in the music file
+    ///
+    /// With `checkpoints`, the progress of each platform is saved to be resumed
+    ///
+    /// In best-effort mode, failed platforms are recorded in the summary instead,
+    /// unless all of them failed
+    /// # Errors
+    /// Fails if fail to get lists, or if the HTTP client is misconfigured
+    async fn export_platforms(
+        &self,
+        existing: &[Music],
+        corrections: &Corrections,
+        checkpoints: bool,
+    ) -> Result<(Vec<Music>, RunSummary), MusicExporterError> {
+        // a misconfigured client fails every platform, so it is not a platform failure
+        self.http_client()?;
+        let known = Arc::new(
+            existing
+                .iter()
+                .filter_map(Music::platform_id)
+                .collect::<HashSet<_>>(),
+        );
+        let now = jiff::Timestamp::now().to_string();
+        let init_lock = tokio::sync::Mutex::new(());
+        let exports = self.platforms.iter().map(|platform_type| {
+            let init_lock = &init_lock;
+            let known = known.clone();
+            let now = &now;
+            async move {
+                let plateform = {
+                    let _guard = init_lock.lock().await;
+                    // the prompts and the OAuth flows wait for the user, so they are cancellable
+                    tokio::select! {
+                        biased;
+                        _ = self.cancel.cancelled() => {
+                            log::warn!("{}: cancelled before the export started", platform_type);
+                            if self.save_on_cancel {
+                                return Ok((vec![], vec![]));
+                            }
+                            return Err(MusicExporterError::new(format!(
+                                "{} export cancelled",
+                                platform_type
+                            ))
+                            .with_kind(ErrorKind::Cancelled));
+                        }
+                        plateform = async {
+                            log::info!("Retrieving music of {}", platform_type);
+                            platform_type.try_init(self).await
+                        } => plateform.map_err(|e| e.with_platform(*platform_type))?,
+                    }
+                };
+                let paginator = Paginator::new(*platform_type, self);
+                let paginator = if checkpoints {
+                    paginator
+                } else {
+                    paginator.without_checkpoint()
+                };
+                let paginator = if self.incremental {
+                    paginator.with_known(known)
+                } else {
+                    paginator
+                };
+                let mut musics = plateform
+                    .get_list(&paginator)
+                    .await
+                    .map_err(|e| e.with_platform(*platform_type))?;
+                for music in &mut musics {
+                    corrections.rules.clean(*platform_type, music);
+                    music.detect_version();
+                    music.first_seen = Some(now.clone());
+                    music.last_seen = Some(now.clone());
+                    music
+                        .sources
+                        .insert(*platform_type, TrackSource::seen(music.url.clone(), now));
+                }
+                log::info!("{}: {} items", platform_type, musics.len());
+                Ok::<_, MusicExporterError>((musics, plateform.skipped()))
+            }
+        });
         let mut items = vec![];
-        for platform_type in &self.platforms {
-            log::info!("Retrieving music of {}", platform_type);
-            let plateform = platform_type.try_init(self).await?;
-            let musics = plateform.get_list().await?;
-            items.extend(musics);
+        let mut summary = RunSummary::default();
+        for (platform, result) in self.platforms.iter().zip(join_all(exports).await) {
+            let outcome = match result {
+                Ok((musics, skipped)) => {
+                    let count = musics.len();
+                    items.extend(musics);
+                    PlatformOutcome {
+                        platform: *platform,
+                        count,
+                        error: None,
+                        skipped,
+                    }
+                }
+                Err(e) if self.best_effort => PlatformOutcome {
+                    platform: *platform,
+                    count: 0,
+                    error: Some(e),
+                    skipped: vec![],
+                },
+                Err(e) => return Err(e),
+            };
+            summary.platforms.push(outcome);
         }
-        Ok(items)
+        if summary.all_failed() {
+            summary.log();
+            summary.check()?;
+        }
+        Ok((items, summary))
     }
 
     /// Write to file
+    ///
+    /// The file is replaced atomically, after rotating the backups.
+    /// Its export date is only updated when the musics changed
     /// # Errors
     /// Error if the file cannot be created
     pub fn write_to_file(&self, data: &[Music]) -> Result<(), MusicExporterError> {
-        let file = File::create(&self.music_file)?;
-        let mut writer = BufWriter::new(file);
-        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
-        let mut ser = serde_json::Serializer::with_formatter(&mut writer, formatter);
-        data.serialize(&mut ser)?;
-        writer.flush()?;
-        Ok(())
+        let mut music_file = fs::read_to_string(&self.music_file)
+            .ok()
+            .and_then(|content| MusicFile::parse(&content).ok())
+            .unwrap_or_else(|| MusicFile::new(vec![], vec![]));
+        music_file.update(data.to_vec(), &self.platforms);
+        self.write_music_file(&music_file)
     }
 
-    /// Read from file
+    /// Write the music file with its metadata
     /// # Errors
     /// Error if the file cannot be created
+    fn write_music_file(&self, music_file: &MusicFile) -> Result<(), MusicExporterError> {
+        storage::rotate_backups(&self.music_file, self.backups)?;
+        storage::write_atomic(&self.music_file, |writer| {
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+            let mut ser = serde_json::Serializer::with_formatter(writer, formatter);
+            music_file.serialize(&mut ser)?;
+            Ok(())
+        })
+    }
+
+    /// Read from file
+    /// # Errors
+    /// Error if the file cannot be created or is not a valid music file (unless recovering)
     pub fn read_from_file(&self) -> Result<Vec<Music>, MusicExporterError> {
+        Ok(self.read_music_file()?.musics)
+    }
+
+    /// Read the music file with its metadata, migrating older versions
+    /// # Errors
+    /// Error if the file cannot be created or is not a valid music file (unless recovering)
+    pub fn read_music_file(&self) -> Result<MusicFile, MusicExporterError> {
         if !self.music_file.exists() {
             if let Some(parent) = self.music_file.parent() {
                 fs::create_dir_all(parent)?;
             }
-            let mut file = OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(&self.music_file)?;
-            file.write_all(b"[]")?;
+            self.write_music_file(&MusicFile::new(vec![], vec![]))?;
+        }
+        self.read_music_file_at(&self.music_file)
+    }
+
+    /// Read a music file with its metadata, migrating older versions
+    /// # Errors
+    /// Error if the file cannot be read or is not a valid music file (unless recovering)
+    fn read_music_file_at(&self, path: &Path) -> Result<MusicFile, MusicExporterError> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            MusicExporterError::new_with_source(format!("Cannot read {}", path.display()), e.into())
+        })?;
+        if content.trim().is_empty() {
+            return Ok(MusicFile::new(vec![], vec![]));
+        }
+        match MusicFile::parse(&content) {
+            Ok(music_file) => Ok(music_file),
+            Err(e) if self.recover => {
+                log::warn!("{}: {}", path.display(), e);
+                let (items, skipped) = storage::recover_musics(&content);
+                log::warn!(
+                    "Recovered {} musics from {} ({} invalid entries skipped)",
+                    items.len(),
+                    path.display(),
+                    skipped
+                );
+                Ok(MusicFile::new(items, vec![]))
+            }
+            Err(e) => {
+                let position = if e.line() > 0 {
+                    format!(" (line {}, column {})", e.line(), e.column())
+                } else {
+                    String::new()
+                };
+                Err(MusicExporterError::new_with_source(
+                    format!(
+                        "Cannot parse {}{}, fix it or use --recover to keep the valid entries",
+                        path.display(),
+                        position
+                    ),
+                    e.into(),
+                ))
+            }
         }
-        let file = File::open(&self.music_file)?;
-        let reader = BufReader::new(file);
-        let mut de = serde_json::Deserializer::from_reader(reader);
-        let items = serde::de::Deserialize::deserialize(&mut de).unwrap_or(vec![]);
-        Ok(items)
     }
 
     /// Get all the platform types
@@ -241,6 +920,41 @@ impl MusicExporter {
     }
 }
 
+/// Corrections of the musics given by the user
+#[derive(Debug)]
+struct Corrections {
+    /// Rules cleaning the titles and authors of the platforms
+    rules: CleaningRules,
+
+    /// Overrides file, if one is given
+    overrides: Option<Overrides>,
+
+    /// Ignored musics
+    ignore: IgnoreList,
+}
+
+impl Corrections {
+    /// Apply the overrides file, if one is given
+    fn correct(&self, musics: &mut [Music]) {
+        if let Some(overrides) = &self.overrides {
+            let changed = overrides.apply(musics.iter_mut());
+            log::info!("Corrected items: {}", changed);
+        }
+    }
+
+    /// Apply the overrides file, then remove the ignored musics
+    ///
+    /// The overrides come first so the corrected musics can be ignored.
+    /// Only used on the fetched musics, the entries of the music file are kept
+    fn apply(&self, musics: &mut Vec<Music>) {
+        self.correct(musics);
+        let ignored = self.ignore.filter(musics);
+        if ignored > 0 {
+            log::info!("Ignored items: {}", ignored);
+        }
+    }
+}
+
 /// Input from the environment
 /// # Errors
 /// Error if the input is not correct
@@ -263,6 +977,22 @@ pub fn input_env(txt: &str, env_name: &str) -> Result<String, MusicExporterError
     Ok(s)
 }
 
+/// Input from the environment without blocking the async runtime
+///
+/// The prompt reads stdin in a blocking thread, so the exports of the
+/// other platforms keep running while waiting for the user
+/// # Errors
+/// Error if the input is not correct
+pub(crate) async fn prompt_env(txt: &str, env_name: &str) -> Result<String, MusicExporterError> {
+    if let Ok(val) = std::env::var(env_name) {
+        return Ok(val);
+    }
+    let (txt, env_name) = (txt.to_string(), env_name.to_string());
+    tokio::task::spawn_blocking(move || input_env(&txt, &env_name))
+        .await
+        .map_err(|e| MusicExporterError::new(format!("Cannot read the input: {}", e)))?
+}
+
 /// Convert to base64
 pub fn to_base_64(input: &str) -> String {
     use base64::Engine;
@@ -1,13 +1,20 @@
 //! Youtube platform implementation
 //! Useful link https://developers.google.com/youtube/v3/docs/playlistItems#resource
 
-use reqwest::Client;
-use std::{future::Future, pin::Pin};
+use std::{collections::HashSet, future::Future, pin::Pin, sync::Mutex};
 
-use super::types::{APIResponse, GoogleAccessToken, PlaylistItems};
+use super::{
+    title,
+    types::{APIResponse, GoogleAccessToken, PlaylistItems, Videos},
+};
 use crate::{
-    custom_env, errors::MusicExporterError, oauth::listen_for_code, utils::input_env, Music,
-    MusicExporter, Platform,
+    custom_env,
+    errors::MusicExporterError,
+    http::{read_token, HttpClient},
+    oauth::listen_for_code,
+    pagination::{Cursor, Page, Paginator},
+    utils::prompt_env,
+    Music, MusicExporter, Platform, PlatformType,
 };
 
 /// Youtube platform
@@ -21,11 +28,31 @@ pub struct YoutubePlatform {
 
     /// custom playlist id
     playlist_id: Option<String>,
+
+    /// HTTP client
+    http: HttpClient,
+
+    /// Whether the "Artist - Title" of the videos are split
+    split_titles: bool,
+
+    /// Whether only the videos of the Music category are kept
+    music_only: bool,
+
+    /// URLs of the videos left out since they are not in the Music category
+    skipped: Mutex<Vec<String>>,
 }
 
+/// Category ID of the Music videos
+const MUSIC_CATEGORY_ID: &str = "10";
+
 /// Youtube redirect URI
 const YOUTUBE_REDIRECT_URI: &str = "http://localhost:8000";
 
+/// URL of a video
+fn video_url(video_id: &str) -> String {
+    format!("https://www.youtube.com/watch?v={}", video_id)
+}
+
 impl YoutubePlatform {
     /// Get the liked playlist id
     /// # Errors
@@ -39,21 +66,18 @@ impl YoutubePlatform {
                 ("key", &self.api_key),
             ],
         )?;
-        let resp = Client::new()
+        let request = self
+            .http
             .get(authorize_url)
             .header("Authorization", format!("Bearer {}", &self.authorization))
-            .header("Accept", "application/json")
-            .send()
-            .await?;
-        let json_response = match resp.status() {
-            reqwest::StatusCode::OK => resp.json::<APIResponse>().await?,
-            err => {
-                return Err(MusicExporterError::new(format!(
-                    "Failed to get of the liked playlist {}",
-                    err
-                )))
-            }
-        };
+            .header("Accept", "application/json");
+        let json_response = self
+            .http
+            .fetch_json::<APIResponse>(PlatformType::Youtube, request)
+            .await
+            .map_err(|e| {
+                MusicExporterError::new_with_source("Failed to get the liked playlist", e)
+            })?;
         match json_response.items.first() {
             Some(first_playlist) => Ok(first_playlist
                 .content_details
@@ -64,15 +88,52 @@ impl YoutubePlatform {
         }
     }
 
+    /// Get the ids of the videos of the Music category
+    /// # Errors
+    /// If the request fails
+    async fn get_music_video_ids(
+        &self,
+        video_ids: &[&str],
+    ) -> Result<HashSet<String>, MusicExporterError> {
+        let url = url::Url::parse_with_params(
+            "https://youtube.googleapis.com/youtube/v3/videos",
+            &[
+                ("part", "snippet"),
+                ("id", &video_ids.join(",")),
+                ("maxResults", "50"),
+                ("key", &self.api_key),
+            ],
+        )?;
+        let request = self
+            .http
+            .get(url)
+            .header("Authorization", format!("Bearer {}", &self.authorization))
+            .header("Accept", "application/json");
+        let json_response = self
+            .http
+            .fetch_json::<Videos>(PlatformType::Youtube, request)
+            .await
+            .map_err(|e| {
+                MusicExporterError::new_with_source("Failed to get the categories of the videos", e)
+            })?;
+        Ok(json_response
+            .items
+            .into_iter()
+            .filter(|video| video.snippet.category_id == MUSIC_CATEGORY_ID)
+            .map(|video| video.id)
+            .collect())
+    }
+
     /// Get the authorization token from the code
     /// # Errors
     /// If the request fails
     async fn code_to_token(
+        http: &HttpClient,
         id_client: &str,
         id_client_secret: &str,
         code: &str,
     ) -> Result<String, MusicExporterError> {
-        let resp = Client::new()
+        let resp = http
             .post("https://oauth2.googleapis.com/token")
             .header("Accept", "application/json")
             .form(&[
@@ -84,51 +145,10 @@ impl YoutubePlatform {
             ])
             .send()
             .await?;
-        let json_response = resp.json::<GoogleAccessToken>().await?;
+        let json_response = read_token::<GoogleAccessToken>(PlatformType::Youtube, resp).await?;
         Ok(json_response.access_token)
     }
 
-    /// Clean the title
-    fn clean_title(title: &str) -> String {
-        let mut parentheses = None;
-        let mut acc = None;
-        let parts = title.bytes().enumerate().map(|(idx, c)| match c {
-            b'(' => {
-                parentheses = Some(idx);
-                None
-            }
-            b'[' => {
-                acc = Some(idx);
-                None
-            }
-            b']' => {
-                if let Some(i) = acc {
-                    acc = None;
-                    Some(title[i..idx + 1].to_string())
-                } else {
-                    None
-                }
-            }
-            b')' => {
-                if let Some(i) = parentheses {
-                    parentheses = None;
-                    Some(title[i..idx + 1].to_string())
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        });
-        let mut cleaned_title = title.to_string();
-        for part in parts.flatten() {
-            if part.to_lowercase().contains("offic") {
-                let to_replace = format!(" {}", part);
-                cleaned_title = cleaned_title.replace(&to_replace, "");
-            }
-        }
-        cleaned_title
-    }
-
     /// Get the playlist items
     /// # Errors
     /// If the request fails
@@ -136,57 +156,85 @@ impl YoutubePlatform {
         &self,
         playlist_id: &str,
         page_token: Option<String>,
-    ) -> Result<(Vec<Music>, Option<String>), MusicExporterError> {
+    ) -> Result<Page, MusicExporterError> {
         let url = url::Url::parse_with_params("https://youtube.googleapis.com/youtube/v3/playlistItems?part=snippet%2CcontentDetails&maxResults=50", 
         &[("playlistId", playlist_id),
         ("key", &self.api_key)])?;
-        let resp = Client::new()
+        let request = self
+            .http
             .get(url)
             .header("Authorization", format!("Bearer {}", &self.authorization))
             .header("Accept", "application/json")
-            .query(&[("pageToken", page_token.unwrap_or_default())])
-            .send()
-            .await?;
-        let json_response = match resp.status() {
-            reqwest::StatusCode::OK => {
-                // on success, parse our JSON to an APIResponse
-                resp.json::<PlaylistItems>().await?
-            }
-            err => {
-                return Err(MusicExporterError::new(format!(
-                    "Failed to get response for the playlist items {}",
-                    err
-                )))
-            }
+            .query(&[("pageToken", page_token.unwrap_or_default())]);
+        let json_response = self
+            .http
+            .fetch_json::<PlaylistItems>(PlatformType::Youtube, request)
+            .await
+            .map_err(|e| {
+                MusicExporterError::new_with_source(
+                    "Failed to get response for the playlist items",
+                    e,
+                )
+            })?;
+        let music_ids = if self.music_only && !json_response.items.is_empty() {
+            let video_ids = json_response
+                .items
+                .iter()
+                .map(|item| item.snippet.resource_id.video_id.as_str())
+                .collect::<Vec<_>>();
+            Some(self.get_music_video_ids(&video_ids).await?)
+        } else {
+            None
         };
+        if let Some(ids) = &music_ids {
+            let skipped = json_response
+                .items
+                .iter()
+                .filter(|item| !ids.contains(&item.snippet.resource_id.video_id))
+                .map(|item| video_url(&item.snippet.resource_id.video_id));
+            self.skipped
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .extend(skipped);
+        }
         let items = json_response
             .items
             .iter()
+            .filter(|item| {
+                music_ids
+                    .as_ref()
+                    .is_none_or(|ids| ids.contains(&item.snippet.resource_id.video_id))
+            })
             .map(|item| {
                 let author = item
                     .snippet
                     .video_owner_channel_title
                     .clone()
-                    .unwrap_or_else(|| "Unknown".to_string())
-                    .replace(" - Topic", "");
-                Music {
-                    title: Self::clean_title(&item.snippet.title),
+                    .unwrap_or_else(|| "Unknown".to_string());
+                let mut music = Music {
+                    title: item.snippet.title.clone(),
                     author,
                     thumbnail: Some(format!(
                         "https://img.youtube.com/vi/{}/default.jpg",
                         item.snippet.resource_id.video_id
                     )),
-                    url: Some(format!(
-                        "https://www.youtube.com/watch?v={}",
-                        item.snippet.resource_id.video_id
-                    )),
+                    url: Some(video_url(&item.snippet.resource_id.video_id)),
                     date: Some(item.snippet.published_at.clone()),
                     album: None,
+                    ..Default::default()
+                };
+                if self.split_titles {
+                    title::split_music(&mut music);
                 }
+                music
             })
             .collect();
         log::info!("Next page token: {:?}", json_response.next_page_token);
-        Ok((items, json_response.next_page_token))
+        Ok(Page {
+            items,
+            next: json_response.next_page_token.map(Cursor::Token),
+            total: None,
+        })
     }
 }
 
@@ -195,39 +243,65 @@ impl Platform for YoutubePlatform {
         music_exp: &MusicExporter,
     ) -> Pin<Box<dyn Future<Output = Result<Self, MusicExporterError>> + Send>> {
         let playlist_id = music_exp.youtube_playlist_id.clone();
-        Box::pin(async {
-            let api_key = input_env("Please enter the youtube developper app API KEY", custom_env!("YOUTUBE_API_KEY"))?;
-            let id_client = input_env("Please enter the youtube developper app 'id_client'", custom_env!("YOUTUBE_ID_CLIENT"))?;
-            let id_client_secret = input_env(
+        let split_titles = !music_exp.keep_youtube_titles;
+        let music_only = music_exp.youtube_music_only;
+        let http = music_exp.http_client();
+        let cancel = music_exp.cancellation_token();
+        Box::pin(async move {
+            let http = http?;
+            let api_key = prompt_env(
+                "Please enter the youtube developper app API KEY",
+                custom_env!("YOUTUBE_API_KEY"),
+            )
+            .await?;
+            let id_client = prompt_env(
+                "Please enter the youtube developper app 'id_client'",
+                custom_env!("YOUTUBE_ID_CLIENT"),
+            )
+            .await?;
+            let id_client_secret = prompt_env(
                 "Please enter the youtube developper app 'id_client_secret'",
                 custom_env!("YOUTUBE_ID_CLIENT_SECRET"),
-            )?;
+            )
+            .await?;
             let scope = "https://www.googleapis.com/auth/youtube.readonly";
             let url_oauth = format!("https://accounts.google.com/o/oauth2/v2/auth?client_id={}&redirect_uri={}&scope={}&response_type=code", id_client.clone(), YOUTUBE_REDIRECT_URI, scope);
             // start the server in a thread
-            let srv = listen_for_code(8000);
+            let srv = listen_for_code(8000, cancel);
             println!(
                 "Please go to this url to get the authorization token (or hit CTRCL+C): {}",
                 url_oauth
             );
             match srv.await {
                 Ok(resp) => {
-                    let authorization =
-                        YoutubePlatform::code_to_token(&id_client, &id_client_secret, &resp.code)
-                            .await?;
+                    let authorization = YoutubePlatform::code_to_token(
+                        &http,
+                        &id_client,
+                        &id_client_secret,
+                        &resp.code,
+                    )
+                    .await?;
                     Ok(Self {
                         api_key,
                         authorization,
                         playlist_id,
+                        http,
+                        split_titles,
+                        music_only,
+                        skipped: Mutex::default(),
                     })
                 }
-                Err(_) => Err(MusicExporterError::new("Failed to get the code")),
+                Err(e) => Err(MusicExporterError::new_with_source(
+                    "Failed to get the code",
+                    e,
+                )),
             }
         })
     }
 
     fn get_list<'a>(
         &'a self,
+        paginator: &'a Paginator,
     ) -> Pin<Box<dyn Future<Output = Result<Vec<Music>, MusicExporterError>> + Send + 'a>> {
         Box::pin(async {
             let playlist_id = if let Some(play_id) = &self.playlist_id {
@@ -238,38 +312,19 @@ impl Platform for YoutubePlatform {
                 log::info!("Liked playlist id: {}", liked_playlist_id);
                 liked_playlist_id
             };
-            let mut items = Vec::new();
-            let mut page_token = None;
-            loop {
-                let (new_items, new_page_token) =
-                    self.get_playlist_items(&playlist_id, page_token).await?;
-                items.extend(new_items);
-                page_token = new_page_token;
-                if page_token.is_none() {
-                    break;
-                }
-            }
-            Ok(items)
+            paginator
+                .collect(|cursor| {
+                    let page_token = cursor.as_ref().and_then(|c| c.token()).map(str::to_string);
+                    self.get_playlist_items(&playlist_id, page_token)
+                })
+                .await
         })
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    /// Test the clean_title function
-    /// # Panics
-    /// If the assertion fails
-    #[test]
-    fn test_clean_title() {
-        let title = "title (feat. artist)";
-        assert_eq!(YoutubePlatform::clean_title(title), "title (feat. artist)");
-        let title = "title (audio officiel)";
-        assert_eq!(YoutubePlatform::clean_title(title), "title");
-        let title = "title [new]";
-        assert_eq!(YoutubePlatform::clean_title(title), "title [new]");
-        let title = "title [official video]";
-        assert_eq!(YoutubePlatform::clean_title(title), "title");
+    fn skipped(&self) -> Vec<String> {
+        self.skipped
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
     }
 }
@@ -1,4 +1,5 @@
 //! Youtube API module
 
 pub mod lib;
+pub mod title;
 pub mod types;
@@ -0,0 +1,159 @@
+//! Parsing of the "Artist - Title" video titles
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::{
+    normalize::{is_version_label, normalize},
+    Music,
+};
+
+/// Separators between the artist and the title
+const SEPARATORS: [&str; 4] = [" - ", " – ", " — ", " -- "];
+
+/// Parts of a video title that are not a title, like "Official Video" or "Clip Officiel"
+static NOT_A_TITLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?:official\b.*|(?:clip|vid[ée]o|audio) officiel(?:le)?|lyrics?(?: video)?|paroles)$",
+    )
+    .expect("valid regex")
+});
+
+/// Suffixes of the channels of labels and music networks
+const CHANNEL_SUFFIXES: [&str; 3] = ["VEVO", "Vevo", "vevo"];
+
+/// Name of the artist of a channel, without the "VEVO" suffix
+///
+/// `"DaftPunkVEVO"` gives `"Daft Punk"`
+pub fn channel_artist(channel: &str) -> String {
+    let Some(name) = CHANNEL_SUFFIXES
+        .iter()
+        .find_map(|suffix| channel.strip_suffix(suffix))
+    else {
+        return channel.to_string();
+    };
+    let name = name.trim();
+    if name.contains(' ') {
+        return name.to_string();
+    }
+    // VEVO channels are in camel case
+    let mut artist = String::new();
+    let mut previous = None;
+    for c in name.chars() {
+        if c.is_uppercase() && previous.is_some_and(char::is_lowercase) {
+            artist.push(' ');
+        }
+        artist.push(c);
+        previous = Some(c);
+    }
+    artist
+}
+
+/// Split a video title into its artist and title
+///
+/// The parts are swapped when the title is "Title - Artist" with the artist being the channel,
+/// and the title is not split when it is "Title - Version" or "Title - Official Video"
+/// (the cleaning rules remove the latter)
+pub fn split_artist_title(title: &str, channel: &str) -> Option<(String, String)> {
+    let (left, right) = SEPARATORS
+        .iter()
+        .find_map(|separator| title.split_once(separator))?;
+    let (left, right) = (left.trim(), right.trim());
+    if left.is_empty() || right.is_empty() || NOT_A_TITLE.is_match(right) {
+        return None;
+    }
+    let channel = normalize(&channel_artist(channel));
+    if normalize(left) == channel {
+        Some((left.to_string(), right.to_string()))
+    } else if normalize(right) == channel {
+        Some((right.to_string(), left.to_string()))
+    } else if is_version_label(right) {
+        None
+    } else {
+        Some((left.to_string(), right.to_string()))
+    }
+}
+
+/// Fill the author and title of a video from its title, keeping the original values
+pub fn split_music(music: &mut Music) {
+    if music.author.ends_with(" - Topic") {
+        // channels generated by Youtube already have the right author and title
+        return;
+    }
+    let (author, title) = match split_artist_title(&music.title, &music.author) {
+        Some(split) => split,
+        None => (channel_artist(&music.author), music.title.clone()),
+    };
+    if author != music.author {
+        music.original_author = Some(std::mem::replace(&mut music.author, author));
+    }
+    if title != music.title {
+        music.original_title = Some(std::mem::replace(&mut music.title, title));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the split of the video titles
+    /// # Panics
+    /// If the assertion fails
+    #[test]
+    fn test_split_music() {
+        let video = |title: &str, author: &str| {
+            let mut music = Music {
+                title: title.to_string(),
+                author: author.to_string(),
+                ..Default::default()
+            };
+            split_music(&mut music);
+            music
+        };
+        let music = video("Daft Punk - One More Time (Official Video)", "DaftPunkVEVO");
+        assert_eq!(music.author, "Daft Punk");
+        assert_eq!(music.title, "One More Time (Official Video)");
+        assert_eq!(music.original_author.as_deref(), Some("DaftPunkVEVO"));
+        assert_eq!(
+            music.original_title.as_deref(),
+            Some("Daft Punk - One More Time (Official Video)")
+        );
+
+        let music = video("Get Lucky – Daft Punk", "Daft Punk");
+        assert_eq!(
+            (music.author.as_str(), music.title.as_str()),
+            ("Daft Punk", "Get Lucky")
+        );
+        assert_eq!(music.original_author, None);
+
+        let music = video("Blue (Da Ba Dee)", "Eiffel65VEVO");
+        assert_eq!(music.author, "Eiffel65");
+        assert_eq!(music.original_title, None);
+        assert_eq!(channel_artist("LadyGagaVEVO"), "Lady Gaga");
+
+        let music = video("Song - Live", "Artist - Topic");
+        assert_eq!(music.title, "Song - Live");
+        assert_eq!(music.original_author, None);
+        let music = video("Song - Radio Edit", "Some Label");
+        assert_eq!(music.title, "Song - Radio Edit");
+        let music = video("Oasis - Live Forever", "OasisVEVO");
+        assert_eq!(music.title, "Live Forever");
+        let music = video("Oasis - Live Forever", "Some Label");
+        assert_eq!(
+            (music.author.as_str(), music.title.as_str()),
+            ("Oasis", "Live Forever")
+        );
+
+        let music = video("Balance ton quoi - Clip Officiel", "Angèle");
+        assert_eq!(
+            (music.author.as_str(), music.title.as_str()),
+            ("Angèle", "Balance ton quoi - Clip Officiel")
+        );
+        let music = video("Never Gonna Give You Up - Official Video", "Rick Astley");
+        assert_eq!(
+            (music.author.as_str(), music.title.as_str()),
+            ("Rick Astley", "Never Gonna Give You Up - Official Video")
+        );
+    }
+}
@@ -8,6 +8,7 @@ pub struct RelatedPlaylists {
     // pub uploads: String,
 }
 
+/// Youtube Content details
 #[derive(Debug, serde::Deserialize)]
 pub struct ContentDetails {
     /// Related playlists
@@ -15,6 +16,7 @@ pub struct ContentDetails {
     pub related_playlists: RelatedPlaylists,
 }
 
+/// Youtube Channel
 #[derive(Debug, serde::Deserialize)]
 pub struct Channel {
     /// contentDetails
@@ -22,12 +24,14 @@ pub struct Channel {
     pub content_details: ContentDetails,
 }
 
+/// Youtube Channels response
 #[derive(Debug, serde::Deserialize)]
 pub struct APIResponse {
     /// Items
     pub items: Vec<Channel>,
 }
 
+/// Google Access token
 #[derive(Debug, serde::Deserialize)]
 pub struct GoogleAccessToken {
     /// Access token
@@ -37,6 +41,7 @@ pub struct GoogleAccessToken {
     // pub token_type: String,
 }
 
+/// Youtube Resource id
 #[derive(Debug, serde::Deserialize)]
 pub struct ResourceIdPlaylistItem {
     /// Video ID
@@ -44,6 +49,7 @@ pub struct ResourceIdPlaylistItem {
     pub video_id: String,
 }
 
+/// Youtube Snippet
 #[derive(Debug, serde::Deserialize)]
 pub struct SnippetPlaylistItem {
     /// Title
@@ -69,6 +75,7 @@ pub struct PlaylistItem {
     pub snippet: SnippetPlaylistItem,
 }
 
+/// Youtube Playlist items
 #[derive(Debug, serde::Deserialize)]
 pub struct PlaylistItems {
     /// Next page token
@@ -77,3 +84,28 @@ pub struct PlaylistItems {
     /// Items
     pub items: Vec<PlaylistItem>,
 }
+
+/// Youtube Video snippet
+#[derive(Debug, serde::Deserialize)]
+pub struct SnippetVideo {
+    /// Category ID ("10" is Music)
+    #[serde(rename = "categoryId")]
+    pub category_id: String,
+}
+
+/// See https://developers.google.com/youtube/v3/docs/videos#resource
+#[derive(Debug, serde::Deserialize)]
+pub struct Video {
+    /// Video ID
+    pub id: String,
+
+    /// Snippet
+    pub snippet: SnippetVideo,
+}
+
+/// Youtube Videos
+#[derive(Debug, serde::Deserialize)]
+pub struct Videos {
+    /// Items
+    pub items: Vec<Video>,
+}
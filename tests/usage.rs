@@ -3,7 +3,12 @@ mod tests {
     use std::path::PathBuf;
 
     use clap::Parser;
-    use music_exporter::{MusicExporter, PlatformType};
+    use music_exporter::{ErrorKind, MusicExporter, PlatformType};
+
+    /// Temporary file unique to this test run
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("music-exporter-{}-{}", std::process::id(), name))
+    }
 
     #[tokio::test]
     async fn parse_failure() {
@@ -18,6 +23,188 @@ mod tests {
         assert_eq!(musics, vec![]);
     }
 
+    #[tokio::test]
+    async fn invalid_certificate() {
+        let mut music_exp = MusicExporter::new_from_vars(
+            PathBuf::from("unknown.json"),
+            None,
+            &[PlatformType::Deezer],
+        );
+        music_exp.ca_cert = vec![PathBuf::from("missing-certificate.pem")];
+        let err = music_exp.get_music_from_platforms().await.unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Config);
+    }
+
+    #[tokio::test]
+    async fn best_effort() {
+        let music_file = temp_file("best-effort.json");
+        let content = r#"[{"author": "a", "title": "one"}]"#;
+        std::fs::write(&music_file, content).unwrap();
+        std::env::set_var("MUSIC_EXPORTER_DEEZER_COOKIE", "cookie");
+        std::env::set_var("MUSIC_EXPORTER_DEEZER_USER_ID", "0");
+        let mut music_exp =
+            MusicExporter::new_from_vars(music_file.clone(), None, &[PlatformType::Deezer]);
+        music_exp.best_effort = true;
+        music_exp.max_retries = 0;
+        // nothing listens on the discard port
+        music_exp.proxy = Some("http://127.0.0.1:9".to_string());
+        let err = music_exp.run_main().await.unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Http { .. }));
+        assert_eq!(std::fs::read_to_string(&music_file).unwrap(), content);
+
+        let mut music_exp =
+            MusicExporter::new_from_vars(music_file.clone(), None, &[PlatformType::Deezer]);
+        music_exp.best_effort = true;
+        music_exp.ca_cert = vec![PathBuf::from("missing-certificate.pem")];
+        let err = music_exp.run_main().await.unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Config);
+        assert_eq!(std::fs::read_to_string(&music_file).unwrap(), content);
+        std::fs::remove_file(&music_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancelled_before_the_export() {
+        let music_exp = MusicExporter::new_from_vars(
+            PathBuf::from("unknown.json"),
+            None,
+            &[PlatformType::Deezer],
+        );
+        music_exp.cancel();
+        let err = music_exp.get_music_from_platforms().await.unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Cancelled);
+
+        let mut music_exp = MusicExporter::new_from_vars(
+            PathBuf::from("unknown.json"),
+            None,
+            &[PlatformType::Deezer],
+        );
+        music_exp.save_on_cancel = true;
+        music_exp.cancel();
+        let musics = music_exp.get_music_from_platforms().await.unwrap();
+        assert_eq!(musics, vec![]);
+    }
+
+    #[tokio::test]
+    async fn write_with_backups() {
+        let music_file = temp_file("backups.json");
+        let mut music_exp = MusicExporter::new_from_vars(music_file.clone(), None, &[]);
+        music_exp.backups = 2;
+        let backup = |idx: usize| PathBuf::from(format!("{}.{}", music_file.display(), idx));
+        for _ in 0..3 {
+            music_exp.write_to_file(&[]).unwrap();
+        }
+        assert!(music_file.exists());
+        assert!(backup(1).exists() && backup(2).exists());
+        assert!(!backup(3).exists());
+        for path in [music_file.clone(), backup(1), backup(2)] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn refuse_invalid_music_file() {
+        let music_file = temp_file("invalid.json");
+        std::fs::write(
+            &music_file,
+            r#"[{"author": "a", "title": "one"}, {"title": }]"#,
+        )
+        .unwrap();
+        let mut music_exp = MusicExporter::new_from_vars(music_file.clone(), None, &[]);
+        let err = music_exp.read_from_file().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Parse);
+        assert!(err.message.contains("line 1"));
+        music_exp.recover = true;
+        let musics = music_exp.read_from_file().unwrap();
+        assert_eq!(musics.len(), 1);
+        std::fs::remove_file(&music_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn keep_user_fields() {
+        let music_file = temp_file("user-fields.json");
+        let content =
+            r#"[{"author": "a", "title": "one", "rating": 5, "tags": ["rock"], "mood": "happy"}]"#;
+        std::fs::write(&music_file, content).unwrap();
+        let music_exp = MusicExporter::new_from_vars(music_file.clone(), None, &[]);
+        let musics = music_exp.read_from_file().unwrap();
+        music_exp.write_to_file(&musics).unwrap();
+        let musics = music_exp.read_from_file().unwrap();
+        assert_eq!(musics[0].rating, Some(5));
+        assert_eq!(musics[0].tags, Some(vec!["rock".to_string()]));
+        assert_eq!(musics[0].extra["mood"], "happy");
+        std::fs::remove_file(&music_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn split_youtube_titles_of_the_file() {
+        let music_file = temp_file("youtube-titles.json");
+        let content = r#"[{
+            "author": "DaftPunkVEVO",
+            "title": "Daft Punk - One More Time (Official Video)",
+            "url": "https://www.youtube.com/watch?v=FGBhQbmPwH8"
+        }, {
+            "author": "Angèle",
+            "title": "Balance ton quoi - Clip Officiel",
+            "url": "https://www.youtube.com/watch?v=Hi7Rx3En7-k"
+        }, {
+            "author": "Rick Astley",
+            "title": "Never Gonna Give You Up - Official Video",
+            "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+        }]"#;
+        std::fs::write(&music_file, content).unwrap();
+        let mut music_exp = MusicExporter::new_from_vars(music_file.clone(), None, &[]);
+        let musics = music_exp.get_musics().await.unwrap();
+        let video = |id: &str| {
+            let url = format!("https://www.youtube.com/watch?v={}", id);
+            let music = musics
+                .iter()
+                .find(|m| m.url.as_ref() == Some(&url))
+                .unwrap();
+            (music.author.as_str(), music.title.as_str())
+        };
+        assert_eq!(video("FGBhQbmPwH8"), ("Daft Punk", "One More Time"));
+        assert_eq!(video("Hi7Rx3En7-k"), ("Angèle", "Balance ton quoi"));
+        assert_eq!(
+            video("dQw4w9WgXcQ"),
+            ("Rick Astley", "Never Gonna Give You Up")
+        );
+        let daft_punk = musics.iter().find(|m| m.author == "Daft Punk").unwrap();
+        assert_eq!(daft_punk.original_author.as_deref(), Some("DaftPunkVEVO"));
+
+        music_exp.keep_youtube_titles = true;
+        let musics = music_exp.get_musics().await.unwrap();
+        assert!(musics.iter().any(|m| m.author == "DaftPunkVEVO"));
+        std::fs::remove_file(&music_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn keep_ignored_musics_of_the_file() {
+        let music_file = temp_file("ignored.json");
+        let ignore_file = temp_file("ignore.json");
+        let content = r#"[{"author": "Some Podcast", "title": "Episode 1", "rating": 4}]"#;
+        std::fs::write(&music_file, content).unwrap();
+        std::fs::write(&ignore_file, r#"{"artists": ["Some Podcast"]}"#).unwrap();
+        let mut music_exp = MusicExporter::new_from_vars(music_file.clone(), None, &[]);
+        music_exp.ignore_file = Some(ignore_file.clone());
+        let musics = music_exp.get_musics().await.unwrap();
+        assert_eq!(musics.len(), 1);
+        assert_eq!(musics[0].rating, Some(4));
+        std::fs::remove_file(&music_file).unwrap();
+        std::fs::remove_file(&ignore_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn diff_refuses_incremental() {
+        let music_file = temp_file("diff.json");
+        std::fs::write(&music_file, "[]").unwrap();
+        let mut music_exp =
+            MusicExporter::new_from_vars(music_file.clone(), None, &[PlatformType::Deezer]);
+        music_exp.incremental = true;
+        let err = music_exp.diff(None, None).await.unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Config);
+        std::fs::remove_file(&music_file).unwrap();
+    }
+
     #[tokio::test]
     async fn test_main() {
         let filename = PathBuf::from("data.json");
@@ -35,5 +222,9 @@ mod tests {
         assert!(main_res.is_err()); // env not found
         let err = main_res.unwrap_err();
         assert_eq!(err.message, "Failed to load env file");
+        assert_eq!(err.kind(), &ErrorKind::Config);
+        let source = std::error::Error::source(&err).expect("the cause is kept");
+        assert_eq!(source.to_string(), "Cannot load .env.fake");
+        assert!(err.report().lines().count() > 2);
     }
 }